@@ -1,15 +1,1290 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    ed25519_program,
+    native_token::LAMPORTS_PER_SOL,
+    program::invoke,
+    system_instruction, system_program,
+    sysvar::{
+        self,
+        instructions::{load_current_index_checked, load_instruction_at_checked},
+    },
+};
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
 
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
+/// The public key of `game-owner.json`, a throwaway localnet keypair that is generated
+/// with `solana-keygen new --outfile game-owner.json` and kept out of version control.
+/// Replace it with the public key of your own keypair before building.
+pub mod game_owner {
+    use super::*;
+
+    declare_id!("ACtBfCe2zXmWxNZhFURkAbBYKYnSRAyBpzd7TCw43HDu");
+}
+
+/// The price of a game until the game owner configures it.
+const DEFAULT_ENTRY_FEE: u64 = LAMPORTS_PER_SOL;
+
 #[program]
 pub mod rock_destroyer {
     use super::*;
 
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+    pub fn initialize_leaderboard(
+        ctx: Context<InitializeLeaderboard>,
+        authority: Pubkey,
+        capacity: u32,
+    ) -> Result<()> {
+        let leaderboard = &mut ctx.accounts.leaderboard;
+
+        // an existing leaderboard keeps its account size
+        require!(
+            (1..=Leaderboard::MAXIMUM_CAPACITY).contains(&capacity)
+                && 8 + Leaderboard::size(capacity) <= leaderboard.to_account_info().data_len(),
+            ErrorCode::InvalidCapacity
+        );
+
+        // `games_played` is kept, so that game nonces are never reused
+        leaderboard.authority = authority;
+        leaderboard.capacity = capacity;
+        leaderboard.players = Vec::new();
+        // entry fees go to the game owner until prizes are configured
+        leaderboard.house_cut = 100;
+        leaderboard.prize_split = Vec::new();
+
+        ctx.accounts.game_config.reset(leaderboard.prize_pool)
+    }
+
+    /// Sets the entry fee, in lamports or in tokens of `mint`.
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        entry_fee: u64,
+        mint: Option<Pubkey>,
+    ) -> Result<()> {
+        let game_config = &mut ctx.accounts.game_config;
+
+        game_config.update(entry_fee, mint, ctx.accounts.leaderboard.prize_pool)
+    }
+
+    /// Sets the percentage of entry fees kept by the game owner, and the
+    /// percentages of the prize pool won by the top players of the season.
+    pub fn configure_prizes(
+        ctx: Context<ConfigurePrizes>,
+        house_cut: u8,
+        prize_split: Vec<u8>,
+    ) -> Result<()> {
+        let leaderboard = &mut ctx.accounts.leaderboard;
+
+        require!(
+            house_cut <= 100
+                && prize_split.len() <= Leaderboard::MAXIMUM_WINNERS
+                && prize_split.iter().all(|&share| share > 0)
+                && (prize_split.is_empty()
+                    || prize_split.iter().map(|&share| share as u32).sum::<u32>() == 100),
+            ErrorCode::InvalidPrizeSplit
+        );
+
+        leaderboard.house_cut = house_cut;
+        leaderboard.prize_split = prize_split;
+
+        Ok(())
+    }
+
+    /// Archives the standings of the current season and opens the next one.
+    pub fn start_season(ctx: Context<StartSeason>) -> Result<()> {
+        let leaderboard = &ctx.accounts.leaderboard;
+        let season_result = &mut ctx.accounts.season_result;
+        let next_leaderboard = &mut ctx.accounts.next_leaderboard;
+
+        season_result.season = leaderboard.season;
+        season_result.ended_at = Clock::get()?.unix_timestamp;
+        season_result.standings = leaderboard.standings();
+        season_result.prize_pool = leaderboard.prize_pool;
+        season_result.prize_split = leaderboard.prize_split.clone();
+        season_result.prize_mint = ctx.accounts.game_config.mint;
+
+        next_leaderboard.authority = leaderboard.authority;
+        next_leaderboard.games_played = leaderboard.games_played;
+        next_leaderboard.capacity = leaderboard.capacity;
+        next_leaderboard.season = leaderboard.season + 1;
+        next_leaderboard.players = Vec::new();
+        next_leaderboard.house_cut = leaderboard.house_cut;
+        next_leaderboard.prize_split = leaderboard.prize_split.clone();
+
+        Ok(())
+    }
+
+    /// Pays the prize pool of an ended season to its top players, who are
+    /// passed as remaining accounts in the order of the standings. Shares
+    /// without a winner, and the rounding, go to the game owner.
+    ///
+    /// Prizes in tokens are paid to the token accounts of the winners instead.
+    pub fn distribute_prizes<'info>(
+        ctx: Context<'_, '_, '_, 'info, DistributePrizes<'info>>,
+    ) -> Result<()> {
+        let season_result = &mut ctx.accounts.season_result;
+        let prize_vault = ctx.accounts.prize_vault.to_account_info();
+
+        require!(season_result.prize_pool > 0, ErrorCode::NoPrizes);
+
+        let prizes = season_result.prizes();
+        require!(
+            ctx.remaining_accounts.len() == prizes.len(),
+            ErrorCode::InvalidPrizeWinner
+        );
+        let rest = season_result.prize_pool - prizes.iter().map(|(_, prize)| prize).sum::<u64>();
+
+        match season_result.prize_mint {
+            None => {
+                let payouts = prizes
+                    .into_iter()
+                    .zip(ctx.remaining_accounts)
+                    .map(|((winner, prize), account)| {
+                        require_keys_eq!(account.key(), winner, ErrorCode::InvalidPrizeWinner);
+                        Ok((account.clone(), prize))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                for (account, prize) in payouts
+                    .into_iter()
+                    .chain([(ctx.accounts.game_owner.to_account_info(), rest)])
+                {
+                    **prize_vault.try_borrow_mut_lamports()? -= prize;
+                    **account.try_borrow_mut_lamports()? += prize;
+                }
+            }
+            Some(mint) => {
+                let (Some(vault_tokens), Some(game_owner_tokens), Some(token_program)) = (
+                    &ctx.accounts.prize_vault_token_account,
+                    &ctx.accounts.game_owner_token_account,
+                    &ctx.accounts.token_program,
+                ) else {
+                    return err!(ErrorCode::MissingTokenAccounts);
+                };
+
+                let payouts = prizes
+                    .into_iter()
+                    .zip(ctx.remaining_accounts)
+                    .map(|((winner, prize), account)| {
+                        let winner_tokens = Account::<TokenAccount>::try_from(account)?;
+                        require!(
+                            winner_tokens.owner == winner && winner_tokens.mint == mint,
+                            ErrorCode::InvalidPrizeWinner
+                        );
+                        Ok((account.clone(), prize))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                let game_owner = ctx.accounts.game_owner.key();
+                let bump = [*ctx.bumps.get("prize_vault").unwrap()];
+                let signer_seeds: &[&[&[u8]]] = &[&[b"prize_vault", game_owner.as_ref(), &bump]];
+
+                for (account, prize) in payouts
+                    .into_iter()
+                    .chain([(game_owner_tokens.to_account_info(), rest)])
+                {
+                    if prize == 0 {
+                        continue;
+                    }
+                    token::transfer(
+                        CpiContext::new_with_signer(
+                            token_program.to_account_info(),
+                            Transfer {
+                                from: vault_tokens.to_account_info(),
+                                to: account,
+                                authority: prize_vault.clone(),
+                            },
+                            signer_seeds,
+                        ),
+                        prize,
+                    )?;
+                }
+            }
+        }
+
+        season_result.prize_pool = 0;
+
+        Ok(())
+    }
+
+    pub fn grow_leaderboard(ctx: Context<GrowLeaderboard>, capacity: u32) -> Result<()> {
+        let leaderboard = &mut ctx.accounts.leaderboard;
+
+        require!(
+            capacity > leaderboard.capacity && capacity <= Leaderboard::MAXIMUM_CAPACITY,
+            ErrorCode::InvalidCapacity
+        );
+        leaderboard.capacity = capacity;
+
+        Ok(())
+    }
+
+    pub fn new_game(ctx: Context<NewGame>, username: String) -> Result<()> {
+        let leaderboard = &mut ctx.accounts.leaderboard;
+
+        if username.len() > Player::MAXIMUM_USERNAME_LENGTH {
+            return Err(ErrorCode::UsernameTooLong.into());
+        }
+
+        let (house, prize) = leaderboard.collect_entry_fee(ctx.accounts.game_config.entry_fee);
+
+        if ctx.accounts.game_config.mint.is_none() {
+            for (recipient, amount) in [
+                (ctx.accounts.game_owner.to_account_info(), house),
+                (ctx.accounts.prize_vault.to_account_info(), prize),
+            ] {
+                if amount == 0 {
+                    continue;
+                }
+                invoke(
+                    &system_instruction::transfer(
+                        &ctx.accounts.user.key(),
+                        &recipient.key(),
+                        amount,
+                    ),
+                    &[
+                        ctx.accounts.user.to_account_info(),
+                        recipient,
+                        ctx.accounts.system_program.to_account_info(),
+                    ],
+                )?;
+            }
+        } else {
+            // the token accounts are checked against the mint by their constraints
+            let (
+                Some(user_tokens),
+                Some(game_owner_tokens),
+                Some(vault_tokens),
+                Some(token_program),
+            ) = (
+                &ctx.accounts.user_token_account,
+                &ctx.accounts.game_owner_token_account,
+                &ctx.accounts.prize_vault_token_account,
+                &ctx.accounts.token_program,
+            )
+            else {
+                return err!(ErrorCode::MissingTokenAccounts);
+            };
+
+            for (recipient, amount) in [(game_owner_tokens, house), (vault_tokens, prize)] {
+                if amount == 0 {
+                    continue;
+                }
+                token::transfer(
+                    CpiContext::new(
+                        token_program.to_account_info(),
+                        Transfer {
+                            from: user_tokens.to_account_info(),
+                            to: recipient.to_account_info(),
+                            authority: ctx.accounts.user.to_account_info(),
+                        },
+                    ),
+                    amount,
+                )?;
+            }
+        }
+
+        leaderboard.new_game(
+            username,
+            ctx.accounts.user.key(),
+            Clock::get()?.unix_timestamp,
+        )?;
+
         Ok(())
     }
+
+    /// The previous instruction must be an Ed25519 signature by the
+    /// leaderboard authority over `score_attestation(user, game_nonce, score)`.
+    pub fn add_player_to_leaderboard(
+        ctx: Context<AddPlayerToLeaderboard>,
+        game_nonce: u64,
+        score: u64,
+    ) -> Result<()> {
+        let leaderboard = &mut ctx.accounts.leaderboard;
+        let user = ctx.accounts.user.key();
+
+        verify_attestation(
+            &ctx.accounts.instructions,
+            &leaderboard.authority,
+            &score_attestation(&user, game_nonce, score),
+        )?;
+
+        leaderboard.add_player(user, game_nonce, score, Clock::get()?.unix_timestamp)
+    }
+
+    /// Returns the 1-based rank of the best score of `player`.
+    pub fn get_rank(ctx: Context<GetRank>, player: Pubkey) -> Result<u32> {
+        ctx.accounts
+            .leaderboard
+            .rank_of(player)
+            .ok_or_else(|| ErrorCode::PlayerNotFound.into())
+    }
 }
 
 #[derive(Accounts)]
-pub struct Initialize {}
+#[instruction(authority: Pubkey, capacity: u32)]
+pub struct InitializeLeaderboard<'info> {
+    #[account(
+        init_if_needed,
+        payer = game_owner,
+        space = 8 + Leaderboard::size(capacity),
+        seeds = [
+            b"leaderboard",
+            game_owner.key().as_ref(),
+            0u32.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub leaderboard: Account<'info, Leaderboard>,
+    /// CHECK: must not exist, so that the first season cannot be reopened
+    #[account(
+        seeds = [
+            b"season_result",
+            game_owner.key().as_ref(),
+            0u32.to_le_bytes().as_ref(),
+        ],
+        bump,
+        constraint = first_season_result.data_is_empty() @ ErrorCode::SeasonEnded
+    )]
+    pub first_season_result: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = game_owner,
+        space = 8,
+        seeds = [b"prize_vault", game_owner.key().as_ref()],
+        bump
+    )]
+    pub prize_vault: Account<'info, PrizeVault>,
+    #[account(
+        init_if_needed,
+        payer = game_owner,
+        space = 8 + GameConfig::SIZE,
+        seeds = [b"game_config", game_owner.key().as_ref()],
+        bump
+    )]
+    pub game_config: Account<'info, GameConfig>,
+    #[account(mut, address = game_owner::ID, owner = system_program::ID)]
+    pub game_owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(mut, seeds = [b"game_config", game_owner.key().as_ref()], bump)]
+    pub game_config: Account<'info, GameConfig>,
+    #[account(
+        seeds = [
+            b"leaderboard",
+            game_owner.key().as_ref(),
+            leaderboard.season.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub leaderboard: Account<'info, Leaderboard>,
+    #[account(address = game_owner::ID)]
+    pub game_owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigurePrizes<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"leaderboard",
+            game_owner.key().as_ref(),
+            leaderboard.season.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub leaderboard: Account<'info, Leaderboard>,
+    #[account(address = game_owner::ID)]
+    pub game_owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DistributePrizes<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"season_result",
+            game_owner.key().as_ref(),
+            season_result.season.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub season_result: Account<'info, SeasonResult>,
+    #[account(mut, seeds = [b"prize_vault", game_owner.key().as_ref()], bump)]
+    pub prize_vault: Account<'info, PrizeVault>,
+    #[account(mut, address = game_owner::ID, owner = system_program::ID)]
+    pub game_owner: Signer<'info>,
+    /// Only for prizes in tokens, like the accounts below.
+    #[account(
+        mut,
+        constraint = Some(prize_vault_token_account.mint) == season_result.prize_mint
+            && prize_vault_token_account.owner == prize_vault.key() @ ErrorCode::InvalidTokenAccount
+    )]
+    pub prize_vault_token_account: Option<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = Some(game_owner_token_account.mint) == season_result.prize_mint
+            && game_owner_token_account.owner == game_owner.key() @ ErrorCode::InvalidTokenAccount
+    )]
+    pub game_owner_token_account: Option<Account<'info, TokenAccount>>,
+    pub token_program: Option<Program<'info, Token>>,
+}
+
+#[derive(Accounts)]
+pub struct StartSeason<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"leaderboard",
+            game_owner.key().as_ref(),
+            leaderboard.season.to_le_bytes().as_ref(),
+        ],
+        bump,
+        close = game_owner
+    )]
+    pub leaderboard: Account<'info, Leaderboard>,
+    #[account(
+        init,
+        payer = game_owner,
+        space = 8 + SeasonResult::size(leaderboard.capacity),
+        seeds = [
+            b"season_result",
+            game_owner.key().as_ref(),
+            leaderboard.season.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub season_result: Account<'info, SeasonResult>,
+    #[account(
+        init,
+        payer = game_owner,
+        space = 8 + Leaderboard::size(leaderboard.capacity),
+        seeds = [
+            b"leaderboard",
+            game_owner.key().as_ref(),
+            (leaderboard.season + 1).to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub next_leaderboard: Account<'info, Leaderboard>,
+    #[account(seeds = [b"game_config", game_owner.key().as_ref()], bump)]
+    pub game_config: Account<'info, GameConfig>,
+    #[account(mut, address = game_owner::ID, owner = system_program::ID)]
+    pub game_owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(capacity: u32)]
+pub struct GrowLeaderboard<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"leaderboard",
+            game_owner.key().as_ref(),
+            leaderboard.season.to_le_bytes().as_ref(),
+        ],
+        bump,
+        realloc = 8 + Leaderboard::size(capacity),
+        realloc::payer = game_owner,
+        realloc::zero = false
+    )]
+    pub leaderboard: Account<'info, Leaderboard>,
+    #[account(mut, address = game_owner::ID, owner = system_program::ID)]
+    pub game_owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct NewGame<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: the game owner only receives the house cut of the entry fee
+    #[account(mut, address = game_owner::ID, owner = system_program::ID)]
+    pub game_owner: AccountInfo<'info>,
+    #[account(mut, seeds = [b"prize_vault", game_owner.key().as_ref()], bump)]
+    pub prize_vault: Account<'info, PrizeVault>,
+    #[account(seeds = [b"game_config", game_owner.key().as_ref()], bump)]
+    pub game_config: Account<'info, GameConfig>,
+    #[account(
+        mut,
+        seeds = [
+            b"leaderboard",
+            game_owner.key().as_ref(),
+            leaderboard.season.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub leaderboard: Account<'info, Leaderboard>,
+    pub system_program: Program<'info, System>,
+    /// Only for entry fees in tokens, like the accounts below.
+    #[account(
+        mut,
+        constraint = Some(user_token_account.mint) == game_config.mint
+            && user_token_account.owner == user.key() @ ErrorCode::InvalidTokenAccount
+    )]
+    pub user_token_account: Option<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = Some(game_owner_token_account.mint) == game_config.mint
+            && game_owner_token_account.owner == game_owner.key() @ ErrorCode::InvalidTokenAccount
+    )]
+    pub game_owner_token_account: Option<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = Some(prize_vault_token_account.mint) == game_config.mint
+            && prize_vault_token_account.owner == prize_vault.key() @ ErrorCode::InvalidTokenAccount
+    )]
+    pub prize_vault_token_account: Option<Account<'info, TokenAccount>>,
+    pub token_program: Option<Program<'info, Token>>,
+}
+
+#[derive(Accounts)]
+pub struct AddPlayerToLeaderboard<'info> {
+    #[account(mut)]
+    pub leaderboard: Account<'info, Leaderboard>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: the instructions sysvar, checked by its address
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetRank<'info> {
+    pub leaderboard: Account<'info, Leaderboard>,
+}
+
+/// The message the game server signs to attest a score.
+pub fn score_attestation(player: &Pubkey, game_nonce: u64, score: u64) -> Vec<u8> {
+    [
+        player.as_ref(),
+        &game_nonce.to_le_bytes(),
+        &score.to_le_bytes(),
+    ]
+    .concat()
+}
+
+/// Checks that the instruction before the current one verified a signature
+/// by `authority` over `message` with the Ed25519 program.
+fn verify_attestation(
+    instructions: &AccountInfo,
+    authority: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let current = load_current_index_checked(instructions)? as usize;
+    require!(current > 0, ErrorCode::InvalidScoreAttestation);
+
+    let attestation = load_instruction_at_checked(current - 1, instructions)?;
+    require!(
+        attestation.program_id == ed25519_program::ID
+            && ed25519_signed_message(&attestation.data) == Some((authority.as_ref(), message)),
+        ErrorCode::InvalidScoreAttestation
+    );
+
+    Ok(())
+}
+
+/// Returns the public key and message of an Ed25519 program instruction
+/// with a single signature, when they are stored in the instruction itself.
+fn ed25519_signed_message(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    // num_signatures: u8, padding: u8, then seven u16 offsets
+    if data.len() < 16 || data[0] != 1 {
+        return None;
+    }
+    let offset = |index: usize| {
+        let at = 2 + index * 2;
+        u16::from_le_bytes([data[at], data[at + 1]])
+    };
+    let signature_instruction = offset(1);
+    let public_key = offset(2) as usize;
+    let public_key_instruction = offset(3);
+    let message = offset(4) as usize;
+    let message_size = offset(5) as usize;
+    let message_instruction = offset(6);
+
+    // `u16::MAX` refers to the Ed25519 instruction itself
+    if [
+        signature_instruction,
+        public_key_instruction,
+        message_instruction,
+    ] != [u16::MAX; 3]
+    {
+        return None;
+    }
+
+    Some((
+        data.get(public_key..public_key + 32)?,
+        data.get(message..message + message_size)?,
+    ))
+}
+
+#[account]
+#[derive(Debug)]
+pub struct Leaderboard {
+    authority: Pubkey,    // 32
+    games_played: u64,    // 8
+    capacity: u32,        // 4
+    season: u32,          // 4
+    players: Vec<Player>, // 4 + (capacity * Player::SIZE)
+    house_cut: u8,        // 1
+    prize_split: Vec<u8>, // 4 + MAXIMUM_WINNERS
+    prize_pool: u64,      // 8
+}
+
+impl Leaderboard {
+    /// Keeps the account within the 10 KiB that can be allocated at once.
+    pub const MAXIMUM_CAPACITY: u32 = 100;
+    pub const MAXIMUM_WINNERS: usize = 10;
+
+    pub fn size(capacity: u32) -> usize {
+        32 + 8
+            + 4
+            + 4
+            + (4 + capacity as usize * Player::SIZE)
+            + 1
+            + (4 + Self::MAXIMUM_WINNERS)
+            + 8
+    }
+
+    /// Splits an entry fee into the house cut and the prize, which is added
+    /// to the prize pool of the season.
+    fn collect_entry_fee(&mut self, fee: u64) -> (u64, u64) {
+        let house = (fee as u128 * self.house_cut as u128 / 100) as u64;
+        let prize = fee - house;
+        self.prize_pool += prize;
+        (house, prize)
+    }
+
+    /// Adds a player who has paid for a game. When the leaderboard is full,
+    /// the lowest recorded score makes room, so games still being played are
+    /// never evicted.
+    fn new_game(&mut self, username: String, pubkey: Pubkey, timestamp: i64) -> Result<()> {
+        if self.players.len() >= self.capacity as usize {
+            let lowest = self
+                .players
+                .iter()
+                .rposition(|player| !player.has_payed)
+                .ok_or(ErrorCode::LeaderboardFull)?;
+            self.players.remove(lowest);
+        }
+
+        let player = Player {
+            username,
+            pubkey,
+            score: 0,
+            has_payed: true,
+            game_nonce: self.games_played,
+            timestamp,
+        };
+        self.games_played += 1;
+        self.insert(player);
+
+        Ok(())
+    }
+
+    /// Records the score of a game the player has paid for.
+    fn add_player(
+        &mut self,
+        pubkey: Pubkey,
+        game_nonce: u64,
+        score: u64,
+        timestamp: i64,
+    ) -> Result<()> {
+        let mut paid_games = self
+            .players
+            .iter()
+            .enumerate()
+            .filter(|(_, player)| player.pubkey == pubkey && player.has_payed)
+            .peekable();
+        require!(paid_games.peek().is_some(), ErrorCode::PlayerNotFound);
+
+        // an attestation for any other game is a replay
+        let (index, _) = paid_games
+            .find(|(_, player)| player.game_nonce == game_nonce)
+            .ok_or(ErrorCode::InvalidScoreAttestation)?;
+
+        let mut player = self.players.remove(index);
+        player.score = score;
+        player.has_payed = false;
+        player.timestamp = timestamp;
+        self.insert(player);
+
+        Ok(())
+    }
+
+    /// Keeps players sorted by score, highest first. Equal scores are ranked
+    /// by timestamp, so the earliest keeps the better rank.
+    fn insert(&mut self, player: Player) {
+        let index = self.players.partition_point(|other| {
+            other.score > player.score
+                || (other.score == player.score && other.timestamp <= player.timestamp)
+        });
+        self.players.insert(index, player);
+    }
+
+    /// The recorded scores, ignoring games still being played.
+    fn standings(&self) -> Vec<Player> {
+        self.players
+            .iter()
+            .filter(|player| !player.has_payed)
+            .cloned()
+            .collect()
+    }
+
+    /// Ranks the recorded scores, ignoring games still being played.
+    fn rank_of(&self, pubkey: Pubkey) -> Option<u32> {
+        self.players
+            .iter()
+            .filter(|player| !player.has_payed)
+            .position(|player| player.pubkey == pubkey)
+            .map(|index| index as u32 + 1)
+    }
+}
+
+/// The final standings of a season. Seasons are numbered from 0, so clients
+/// can list past seasons by deriving the results up to `Leaderboard::season`.
+#[account]
+pub struct SeasonResult {
+    season: u32,                // 4
+    ended_at: i64,              // 8
+    standings: Vec<Player>,     // 4 + (capacity * Player::SIZE)
+    prize_pool: u64,            // 8
+    prize_split: Vec<u8>,       // 4 + Leaderboard::MAXIMUM_WINNERS
+    prize_mint: Option<Pubkey>, // 1 + 32
+}
+
+impl SeasonResult {
+    pub fn size(capacity: u32) -> usize {
+        4 + 8
+            + (4 + capacity as usize * Player::SIZE)
+            + 8
+            + (4 + Leaderboard::MAXIMUM_WINNERS)
+            + (1 + 32)
+    }
+
+    /// The prize of each of the top players, in the order of the standings.
+    /// Only the best entry of a player counts, so a player wins one prize at most.
+    fn prizes(&self) -> Vec<(Pubkey, u64)> {
+        let mut winners = Vec::new();
+        for player in &self.standings {
+            if !winners.contains(&player.pubkey) {
+                winners.push(player.pubkey);
+            }
+        }
+
+        winners
+            .into_iter()
+            .zip(&self.prize_split)
+            .map(|(winner, &share)| {
+                let prize = self.prize_pool as u128 * share as u128 / 100;
+                (winner, prize as u64)
+            })
+            .collect()
+    }
+}
+
+/// Holds the prize pools of the seasons until they are distributed.
+#[account]
+pub struct PrizeVault {}
+
+/// The entry fee is in lamports, or in the smallest unit of `mint`.
+#[account]
+pub struct GameConfig {
+    entry_fee: u64,       // 8
+    mint: Option<Pubkey>, // 1 + 32
+}
+
+impl GameConfig {
+    pub const SIZE: usize = 8 + (1 + 32);
+
+    /// The prize pool of the current season must be empty to change
+    /// the mint, as it is paid out in a single currency.
+    fn update(&mut self, entry_fee: u64, mint: Option<Pubkey>, prize_pool: u64) -> Result<()> {
+        require!(
+            mint == self.mint || prize_pool == 0,
+            ErrorCode::PrizePoolNotEmpty
+        );
+
+        self.entry_fee = entry_fee;
+        self.mint = mint;
+
+        Ok(())
+    }
+
+    /// Restores the default entry fee, in lamports. Re-initializing an open
+    /// first season must not strand a prize pool held in tokens.
+    fn reset(&mut self, prize_pool: u64) -> Result<()> {
+        self.update(DEFAULT_ENTRY_FEE, None, prize_pool)
+    }
+}
+
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub struct Player {
+    /// Max 32 bytes.
+    pub username: String,
+    pub pubkey: Pubkey,
+    pub score: u64,
+    /// Whether the player has paid for a game without a score yet.
+    pub has_payed: bool,
+    /// Identifies the game in score attestations.
+    pub game_nonce: u64,
+    /// When the game started, or when its score was recorded.
+    pub timestamp: i64,
+}
+
+impl Player {
+    pub const MAXIMUM_USERNAME_LENGTH: usize = 32;
+    pub const SIZE: usize = (4 + Self::MAXIMUM_USERNAME_LENGTH) + 32 + 8 + 1 + 8 + 8;
+}
+
+#[error_code]
+pub enum ErrorCode {
+    #[msg("No player with the given public key has paid for a game.")]
+    PlayerNotFound,
+    #[msg("The username must be at most 32 bytes.")]
+    UsernameTooLong,
+    #[msg("The score must be attested by the game server for this game.")]
+    InvalidScoreAttestation,
+    #[msg("The capacity must grow, up to 100 players.")]
+    InvalidCapacity,
+    #[msg("The season has already ended.")]
+    SeasonEnded,
+    #[msg("The house cut must be at most 100%, and the prize split must add up to 100%.")]
+    InvalidPrizeSplit,
+    #[msg("The winners must be passed in the order of the standings.")]
+    InvalidPrizeWinner,
+    #[msg("There are no prizes to distribute.")]
+    NoPrizes,
+    #[msg("Payments in tokens need the token accounts and the token program.")]
+    MissingTokenAccounts,
+    #[msg("The token account has the wrong mint or owner.")]
+    InvalidTokenAccount,
+    #[msg("The mint cannot change while the season has a prize pool.")]
+    PrizePoolNotEmpty,
+    #[msg("Every place on the leaderboard is taken by a game being played.")]
+    LeaderboardFull,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaderboard(players: Vec<Player>) -> Leaderboard {
+        let mut leaderboard = Leaderboard {
+            authority: Pubkey::new_unique(),
+            games_played: players.len() as u64,
+            capacity: 5,
+            season: 0,
+            players: vec![],
+            house_cut: 100,
+            prize_split: vec![],
+            prize_pool: 0,
+        };
+        for player in players {
+            leaderboard.insert(player);
+        }
+        leaderboard
+    }
+
+    fn player(username: &str, score: u64, has_payed: bool) -> Player {
+        Player {
+            username: username.to_string(),
+            pubkey: Pubkey::new_unique(),
+            score,
+            has_payed,
+            game_nonce: 0,
+            timestamp: 0,
+        }
+    }
+
+    fn scores(leaderboard: &Leaderboard) -> Vec<u64> {
+        leaderboard
+            .players
+            .iter()
+            .map(|player| player.score)
+            .collect()
+    }
+
+    /// Lays out an Ed25519 program instruction like `@solana/web3.js` does.
+    fn ed25519_data(public_key: &Pubkey, message: &[u8]) -> Vec<u8> {
+        let offsets: [u16; 7] = [
+            48,
+            u16::MAX,
+            16,
+            u16::MAX,
+            112,
+            message.len() as u16,
+            u16::MAX,
+        ];
+        let mut data = vec![1, 0];
+        for offset in offsets {
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+        data.extend_from_slice(public_key.as_ref());
+        data.extend_from_slice(&[0; 64]);
+        data.extend_from_slice(message);
+        data
+    }
+
+    #[test]
+    fn new_game_adds_a_paying_player() {
+        let mut leaderboard = leaderboard(vec![]);
+        let pubkey = Pubkey::new_unique();
+
+        leaderboard
+            .new_game("camperbot".to_string(), pubkey, 10)
+            .unwrap();
+        leaderboard
+            .new_game("camperbot".to_string(), pubkey, 20)
+            .unwrap();
+
+        assert_eq!(
+            leaderboard.players,
+            vec![
+                Player {
+                    username: "camperbot".to_string(),
+                    pubkey,
+                    score: 0,
+                    has_payed: true,
+                    game_nonce: 0,
+                    timestamp: 10,
+                },
+                Player {
+                    username: "camperbot".to_string(),
+                    pubkey,
+                    score: 0,
+                    has_payed: true,
+                    game_nonce: 1,
+                    timestamp: 20,
+                }
+            ]
+        );
+        assert_eq!(leaderboard.games_played, 2);
+    }
+
+    #[test]
+    fn new_game_replaces_the_lowest_score_when_full() {
+        let mut leaderboard = leaderboard(
+            [30, 10, 50, 20, 40]
+                .into_iter()
+                .map(|score| player("player", score, false))
+                .collect(),
+        );
+        let pubkey = Pubkey::new_unique();
+
+        assert_eq!(scores(&leaderboard), vec![50, 40, 30, 20, 10]);
+
+        leaderboard
+            .new_game("newcomer".to_string(), pubkey, 10)
+            .unwrap();
+
+        assert_eq!(scores(&leaderboard), vec![50, 40, 30, 20, 0]);
+        assert_eq!(leaderboard.players[4].pubkey, pubkey);
+    }
+
+    #[test]
+    fn new_game_keeps_games_being_played_when_full() {
+        let mut leaderboard = leaderboard(
+            [30, 10, 50, 20, 40]
+                .into_iter()
+                .map(|score| player("player", score, false))
+                .collect(),
+        );
+        let first = Pubkey::new_unique();
+        let second = Pubkey::new_unique();
+
+        leaderboard
+            .new_game("first".to_string(), first, 10)
+            .unwrap();
+        leaderboard
+            .new_game("second".to_string(), second, 20)
+            .unwrap();
+
+        assert_eq!(scores(&leaderboard), vec![50, 40, 30, 0, 0]);
+        assert_eq!(leaderboard.players[3].pubkey, first);
+        assert_eq!(leaderboard.players[4].pubkey, second);
+        leaderboard.add_player(first, 5, 100, 30).unwrap();
+    }
+
+    #[test]
+    fn new_game_requires_a_recorded_score_to_replace() {
+        let mut leaderboard = leaderboard((0..5).map(|_| player("player", 0, true)).collect());
+
+        assert_eq!(
+            leaderboard
+                .new_game("newcomer".to_string(), Pubkey::new_unique(), 10)
+                .unwrap_err(),
+            error!(ErrorCode::LeaderboardFull)
+        );
+        assert_eq!(leaderboard.players.len(), 5);
+        assert_eq!(leaderboard.games_played, 5);
+    }
+
+    #[test]
+    fn add_player_records_the_score_of_a_paid_game() {
+        let mut leaderboard = leaderboard(vec![player("camperbot", 0, true)]);
+        let pubkey = leaderboard.players[0].pubkey;
+
+        leaderboard.add_player(pubkey, 0, 100, 10).unwrap();
+
+        assert_eq!(leaderboard.players[0].score, 100);
+        assert!(!leaderboard.players[0].has_payed);
+        assert_eq!(leaderboard.players[0].timestamp, 10);
+    }
+
+    #[test]
+    fn add_player_requires_a_paid_game() {
+        let mut leaderboard = leaderboard(vec![player("camperbot", 100, false)]);
+        let pubkey = leaderboard.players[0].pubkey;
+
+        assert_eq!(
+            leaderboard.add_player(pubkey, 0, 200, 10).unwrap_err(),
+            error!(ErrorCode::PlayerNotFound)
+        );
+        assert_eq!(
+            leaderboard
+                .add_player(Pubkey::new_unique(), 0, 200, 10)
+                .unwrap_err(),
+            error!(ErrorCode::PlayerNotFound)
+        );
+        assert_eq!(leaderboard.players[0].score, 100);
+    }
+
+    #[test]
+    fn add_player_rejects_the_nonce_of_another_game() {
+        let mut leaderboard = leaderboard(vec![]);
+        let pubkey = Pubkey::new_unique();
+        leaderboard
+            .new_game("camperbot".to_string(), pubkey, 10)
+            .unwrap();
+        leaderboard.add_player(pubkey, 0, 100, 20).unwrap();
+        leaderboard
+            .new_game("camperbot".to_string(), pubkey, 30)
+            .unwrap();
+
+        // replaying the attestation of the first game
+        assert_eq!(
+            leaderboard.add_player(pubkey, 0, 100, 40).unwrap_err(),
+            error!(ErrorCode::InvalidScoreAttestation)
+        );
+
+        leaderboard.add_player(pubkey, 1, 200, 40).unwrap();
+        assert_eq!(scores(&leaderboard), vec![200, 100]);
+    }
+
+    #[test]
+    fn add_player_ranks_equal_scores_by_timestamp() {
+        let mut leaderboard = leaderboard(vec![]);
+        let [first, second, third] = [(); 3].map(|_| Pubkey::new_unique());
+        leaderboard
+            .new_game("first".to_string(), first, 10)
+            .unwrap();
+        leaderboard
+            .new_game("second".to_string(), second, 20)
+            .unwrap();
+        leaderboard
+            .new_game("third".to_string(), third, 30)
+            .unwrap();
+
+        leaderboard.add_player(second, 1, 50, 40).unwrap();
+        leaderboard.add_player(third, 2, 100, 50).unwrap();
+        leaderboard.add_player(first, 0, 50, 60).unwrap();
+
+        assert_eq!(
+            leaderboard
+                .players
+                .iter()
+                .map(|player| player.pubkey)
+                .collect::<Vec<_>>(),
+            vec![third, second, first]
+        );
+        assert_eq!(leaderboard.rank_of(third), Some(1));
+        assert_eq!(leaderboard.rank_of(second), Some(2));
+        assert_eq!(leaderboard.rank_of(first), Some(3));
+    }
+
+    #[test]
+    fn rank_of_ignores_games_being_played() {
+        let mut leaderboard = leaderboard(vec![player("camperbot", 100, false)]);
+        let pubkey = Pubkey::new_unique();
+        leaderboard
+            .new_game("newcomer".to_string(), pubkey, 10)
+            .unwrap();
+
+        assert_eq!(leaderboard.rank_of(pubkey), None);
+
+        leaderboard.add_player(pubkey, 1, 200, 20).unwrap();
+        leaderboard
+            .new_game("newcomer".to_string(), pubkey, 30)
+            .unwrap();
+
+        assert_eq!(leaderboard.rank_of(pubkey), Some(1));
+        assert_eq!(leaderboard.rank_of(leaderboard.players[1].pubkey), Some(2));
+    }
+
+    #[test]
+    fn standings_leave_out_games_being_played() {
+        let mut leaderboard = leaderboard(vec![
+            player("first", 100, false),
+            player("second", 50, false),
+        ]);
+        leaderboard
+            .new_game("newcomer".to_string(), Pubkey::new_unique(), 10)
+            .unwrap();
+
+        assert_eq!(leaderboard.players.len(), 3);
+        assert_eq!(leaderboard.standings(), leaderboard.players[..2].to_vec());
+    }
+
+    #[test]
+    fn collect_entry_fee_adds_to_the_prize_pool() {
+        let mut leaderboard = leaderboard(vec![]);
+
+        assert_eq!(leaderboard.collect_entry_fee(1_000), (1_000, 0));
+
+        leaderboard.house_cut = 10;
+        assert_eq!(leaderboard.collect_entry_fee(1_000), (100, 900));
+        assert_eq!(leaderboard.collect_entry_fee(999), (99, 900));
+        assert_eq!(leaderboard.prize_pool, 1_800);
+    }
+
+    #[test]
+    fn prizes_split_the_pool_between_the_top_players() {
+        let standings = vec![
+            player("first", 300, false),
+            player("second", 200, false),
+            player("third", 100, false),
+        ];
+        let mut season_result = SeasonResult {
+            season: 0,
+            ended_at: 0,
+            standings: standings.clone(),
+            prize_pool: 1_001,
+            prize_split: vec![50, 30, 20],
+            prize_mint: None,
+        };
+
+        assert_eq!(
+            season_result.prizes(),
+            vec![
+                (standings[0].pubkey, 500),
+                (standings[1].pubkey, 300),
+                (standings[2].pubkey, 200),
+            ]
+        );
+
+        // shares without a winner are not paid out
+        season_result.standings.truncate(1);
+        assert_eq!(season_result.prizes(), vec![(standings[0].pubkey, 500)]);
+    }
+
+    #[test]
+    fn prizes_go_to_the_best_entry_of_each_player() {
+        let first = player("first", 300, false);
+        let second = player("second", 100, false);
+        let mut season_result = SeasonResult {
+            season: 0,
+            ended_at: 0,
+            standings: vec![
+                first.clone(),
+                Player {
+                    score: 200,
+                    ..first.clone()
+                },
+                second.clone(),
+            ],
+            prize_pool: 1_000,
+            prize_split: vec![50, 30, 20],
+            prize_mint: None,
+        };
+
+        assert_eq!(
+            season_result.prizes(),
+            vec![(first.pubkey, 500), (second.pubkey, 300)]
+        );
+
+        season_result.standings.remove(2);
+        assert_eq!(season_result.prizes(), vec![(first.pubkey, 500)]);
+    }
+
+    #[test]
+    fn update_keeps_the_mint_of_a_prize_pool() {
+        let mint = Pubkey::new_unique();
+        let mut game_config = GameConfig {
+            entry_fee: DEFAULT_ENTRY_FEE,
+            mint: None,
+        };
+
+        game_config.update(1_000, Some(mint), 0).unwrap();
+        game_config.update(2_000, Some(mint), 900).unwrap();
+
+        assert_eq!(
+            game_config
+                .update(DEFAULT_ENTRY_FEE, None, 900)
+                .unwrap_err(),
+            error!(ErrorCode::PrizePoolNotEmpty)
+        );
+        assert_eq!(game_config.entry_fee, 2_000);
+        assert_eq!(game_config.mint, Some(mint));
+    }
+
+    #[test]
+    fn reset_keeps_the_mint_of_a_prize_pool() {
+        let mint = Pubkey::new_unique();
+        // a newly created config is zeroed
+        let mut game_config = GameConfig {
+            entry_fee: 0,
+            mint: None,
+        };
+
+        game_config.reset(0).unwrap();
+        assert_eq!(game_config.entry_fee, DEFAULT_ENTRY_FEE);
+        assert_eq!(game_config.mint, None);
+
+        game_config.update(100, Some(mint), 0).unwrap();
+        assert_eq!(
+            game_config.reset(90).unwrap_err(),
+            error!(ErrorCode::PrizePoolNotEmpty)
+        );
+        assert_eq!(game_config.entry_fee, 100);
+        assert_eq!(game_config.mint, Some(mint));
+
+        game_config.reset(0).unwrap();
+        assert_eq!(game_config.mint, None);
+    }
+
+    #[test]
+    fn ed25519_signed_message_reads_the_public_key_and_message() {
+        let authority = Pubkey::new_unique();
+        let message = score_attestation(&Pubkey::new_unique(), 7, 100);
+
+        assert_eq!(
+            ed25519_signed_message(&ed25519_data(&authority, &message)),
+            Some((authority.as_ref(), message.as_slice()))
+        );
+    }
+
+    #[test]
+    fn ed25519_signed_message_rejects_data_from_other_instructions() {
+        let authority = Pubkey::new_unique();
+        let message = score_attestation(&Pubkey::new_unique(), 7, 100);
+        let mut data = ed25519_data(&authority, &message);
+        // the message lives in instruction 0
+        data[14..16].copy_from_slice(&0u16.to_le_bytes());
+
+        assert_eq!(ed25519_signed_message(&data), None);
+        assert_eq!(ed25519_signed_message(&data[..15]), None);
+    }
+
+    #[test]
+    fn ed25519_signed_message_rejects_out_of_bounds_offsets() {
+        let authority = Pubkey::new_unique();
+        let message = score_attestation(&Pubkey::new_unique(), 7, 100);
+        let data = ed25519_data(&authority, &message);
+
+        assert_eq!(ed25519_signed_message(&data[..data.len() - 1]), None);
+    }
+}
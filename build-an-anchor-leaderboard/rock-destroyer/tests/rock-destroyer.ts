@@ -1,16 +1,657 @@
 import * as anchor from "@coral-xyz/anchor";
-import { Program } from "@coral-xyz/anchor";
+import { AnchorError, Program } from "@coral-xyz/anchor";
 import { RockDestroyer } from "../target/types/rock_destroyer";
+import {
+  Connection,
+  Ed25519Program,
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  sendAndConfirmTransaction,
+  SystemProgram,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
+  Transaction,
+  TransactionInstruction,
+} from "@solana/web3.js";
+import { expect } from "chai";
+import { readFileSync } from "fs";
 
 describe("rock-destroyer", () => {
   // Configure the client to use the local cluster.
   anchor.setProvider(anchor.AnchorProvider.env());
 
   const program = anchor.workspace.RockDestroyer as Program<RockDestroyer>;
+  const connection = program.provider.connection;
 
-  it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods.initialize().rpc();
-    console.log("Your transaction signature", tx);
+  // Generated with `solana-keygen new --outfile game-owner.json`, and must
+  // match `game_owner::ID` in the program.
+  const gameOwner = Keypair.fromSecretKey(
+    Uint8Array.from(JSON.parse(readFileSync("game-owner.json", "utf-8")))
+  );
+  const leaderboardPublicKey = seasonAddress(
+    program,
+    gameOwner,
+    "leaderboard",
+    0
+  );
+  const firstSeasonResultPublicKey = seasonAddress(
+    program,
+    gameOwner,
+    "season_result",
+    0
+  );
+  const [prizeVaultPublicKey] = PublicKey.findProgramAddressSync(
+    [Buffer.from("prize_vault"), gameOwner.publicKey.toBuffer()],
+    program.programId
+  );
+  const [gameConfigPublicKey] = PublicKey.findProgramAddressSync(
+    [Buffer.from("game_config"), gameOwner.publicKey.toBuffer()],
+    program.programId
+  );
+  const user = Keypair.generate();
+  const server = Keypair.generate();
+
+  const newGame = (
+    leaderboard: PublicKey,
+    tokenAccounts: TokenAccounts = noTokenAccounts
+  ) =>
+    program.methods
+      .newGame("camperbot")
+      .accounts({
+        user: user.publicKey,
+        gameOwner: gameOwner.publicKey,
+        prizeVault: prizeVaultPublicKey,
+        gameConfig: gameConfigPublicKey,
+        leaderboard,
+        ...tokenAccounts,
+      })
+      .signers([user])
+      .rpc();
+
+  before(async () => {
+    for (const keypair of [gameOwner, user]) {
+      const sg = await connection.requestAirdrop(
+        keypair.publicKey,
+        5 * LAMPORTS_PER_SOL
+      );
+      await connection.confirmTransaction(sg);
+    }
+  });
+
+  it("initializes leaderboard", async () => {
+    await program.methods
+      .initializeLeaderboard(server.publicKey, 5)
+      .accounts({
+        leaderboard: leaderboardPublicKey,
+        firstSeasonResult: firstSeasonResultPublicKey,
+        prizeVault: prizeVaultPublicKey,
+        gameConfig: gameConfigPublicKey,
+        gameOwner: gameOwner.publicKey,
+      })
+      .signers([gameOwner])
+      .rpc();
+
+    const leaderboard = await program.account.leaderboard.fetch(
+      leaderboardPublicKey
+    );
+    expect(leaderboard.capacity).to.equal(5);
+    expect(leaderboard.players).to.eql([]);
+  });
+
+  it("creates a new game", async () => {
+    const gameOwnerBalance = await connection.getBalance(gameOwner.publicKey);
+
+    await newGame(leaderboardPublicKey);
+
+    expect(await connection.getBalance(gameOwner.publicKey)).to.equal(
+      gameOwnerBalance + LAMPORTS_PER_SOL
+    );
+    const leaderboard = await program.account.leaderboard.fetch(
+      leaderboardPublicKey
+    );
+    expect(leaderboard.players).to.have.lengthOf(1);
+    expect(leaderboard.players[0].username).to.equal("camperbot");
+    expect(leaderboard.players[0].pubkey).to.eql(user.publicKey);
+    expect(leaderboard.players[0].score.toNumber()).to.equal(0);
+    expect(leaderboard.players[0].hasPayed).to.be.true;
+  });
+
+  it("adds a player to the leaderboard", async () => {
+    let leaderboard = await program.account.leaderboard.fetch(
+      leaderboardPublicKey
+    );
+    const gameNonce = leaderboard.players[0].gameNonce;
+
+    await addScore(program, server, user, leaderboardPublicKey, gameNonce, 100);
+
+    leaderboard = await program.account.leaderboard.fetch(leaderboardPublicKey);
+    expect(leaderboard.players[0].score.toNumber()).to.equal(100);
+    expect(leaderboard.players[0].hasPayed).to.be.false;
+  });
+
+  it("throws an error when the user has not payed", async () => {
+    const leaderboard = await program.account.leaderboard.fetch(
+      leaderboardPublicKey
+    );
+    const gameNonce = leaderboard.players[0].gameNonce;
+
+    try {
+      await addScore(
+        program,
+        server,
+        user,
+        leaderboardPublicKey,
+        gameNonce,
+        200
+      );
+      expect.fail("should've failed but didn't");
+    } catch (_err) {
+      expect(_err).to.be.instanceOf(AnchorError);
+      const err: AnchorError = _err;
+      expect(err.error.errorCode.code).to.equal("PlayerNotFound");
+    }
+  });
+
+  it("only accepts scores attested by the game server", async () => {
+    await newGame(leaderboardPublicKey);
+    const leaderboard = await program.account.leaderboard.fetch(
+      leaderboardPublicKey
+    );
+    const [firstGame, secondGame] = leaderboard.players.map(
+      (player) => player.gameNonce
+    );
+
+    const attempts: Array<[Keypair, anchor.BN]> = [
+      // signed by someone else
+      [Keypair.generate(), secondGame],
+      // replaying the attestation of the first game
+      [server, firstGame],
+    ];
+    for (const [authority, gameNonce] of attempts) {
+      try {
+        await addScore(
+          program,
+          authority,
+          user,
+          leaderboardPublicKey,
+          gameNonce,
+          1_000_000
+        );
+        expect.fail("should've failed but didn't");
+      } catch (_err) {
+        expect(_err).to.be.instanceOf(AnchorError);
+        const err: AnchorError = _err;
+        expect(err.error.errorCode.code).to.equal("InvalidScoreAttestation");
+      }
+    }
+
+    await addScore(program, server, user, leaderboardPublicKey, secondGame, 50);
+  });
+
+  it("ranks players by their best score", async () => {
+    const leaderboard = await program.account.leaderboard.fetch(
+      leaderboardPublicKey
+    );
+    expect(
+      leaderboard.players.map((player) => player.score.toNumber())
+    ).to.eql([100, 50]);
+
+    const rank = await program.methods
+      .getRank(user.publicKey)
+      .accounts({ leaderboard: leaderboardPublicKey })
+      .view();
+    expect(rank).to.equal(1);
+  });
+
+  it("grows the leaderboard", async () => {
+    await program.methods
+      .growLeaderboard(10)
+      .accounts({
+        leaderboard: leaderboardPublicKey,
+        gameOwner: gameOwner.publicKey,
+      })
+      .signers([gameOwner])
+      .rpc();
+
+    const leaderboard = await program.account.leaderboard.fetch(
+      leaderboardPublicKey
+    );
+    expect(leaderboard.capacity).to.equal(10);
+    // discriminator, authority, games played, capacity, season,
+    // 10 players of 93 bytes, house cut, prize split and prize pool
+    const accountInfo = await connection.getAccountInfo(leaderboardPublicKey);
+    expect(accountInfo.data.length).to.equal(
+      8 + 32 + 8 + 4 + 4 + (4 + 10 * 93) + 1 + (4 + 10) + 8
+    );
+
+    try {
+      await program.methods
+        .growLeaderboard(5)
+        .accounts({
+          leaderboard: leaderboardPublicKey,
+          gameOwner: gameOwner.publicKey,
+        })
+        .signers([gameOwner])
+        .rpc();
+      expect.fail("should've failed but didn't");
+    } catch (_err) {
+      expect(_err).to.be.instanceOf(AnchorError);
+      const err: AnchorError = _err;
+      expect(err.error.errorCode.code).to.equal("InvalidCapacity");
+    }
+  });
+
+  it("collects entry fees into the prize pool", async () => {
+    try {
+      await program.methods
+        .configurePrizes(10, Buffer.from([70, 20]))
+        .accounts({
+          leaderboard: leaderboardPublicKey,
+          gameOwner: gameOwner.publicKey,
+        })
+        .signers([gameOwner])
+        .rpc();
+      expect.fail("should've failed but didn't");
+    } catch (_err) {
+      expect(_err).to.be.instanceOf(AnchorError);
+      const err: AnchorError = _err;
+      expect(err.error.errorCode.code).to.equal("InvalidPrizeSplit");
+    }
+
+    await program.methods
+      .configurePrizes(10, Buffer.from([70, 30]))
+      .accounts({
+        leaderboard: leaderboardPublicKey,
+        gameOwner: gameOwner.publicKey,
+      })
+      .signers([gameOwner])
+      .rpc();
+
+    const gameOwnerBalance = await connection.getBalance(gameOwner.publicKey);
+    const prizeVaultBalance = await connection.getBalance(prizeVaultPublicKey);
+
+    await newGame(leaderboardPublicKey);
+
+    expect(await connection.getBalance(gameOwner.publicKey)).to.equal(
+      gameOwnerBalance + 0.1 * LAMPORTS_PER_SOL
+    );
+    expect(await connection.getBalance(prizeVaultPublicKey)).to.equal(
+      prizeVaultBalance + 0.9 * LAMPORTS_PER_SOL
+    );
+    const leaderboard = await program.account.leaderboard.fetch(
+      leaderboardPublicKey
+    );
+    expect(leaderboard.prizePool.toNumber()).to.equal(0.9 * LAMPORTS_PER_SOL);
+  });
+
+  it("starts a new season", async () => {
+    await program.methods
+      .startSeason()
+      .accounts({
+        leaderboard: leaderboardPublicKey,
+        seasonResult: firstSeasonResultPublicKey,
+        nextLeaderboard: seasonAddress(program, gameOwner, "leaderboard", 1),
+        gameConfig: gameConfigPublicKey,
+        gameOwner: gameOwner.publicKey,
+      })
+      .signers([gameOwner])
+      .rpc();
+
+    expect(await connection.getAccountInfo(leaderboardPublicKey)).to.be.null;
+    const leaderboard = await program.account.leaderboard.fetch(
+      seasonAddress(program, gameOwner, "leaderboard", 1)
+    );
+    expect(leaderboard.season).to.equal(1);
+    expect(leaderboard.capacity).to.equal(10);
+    expect(leaderboard.players).to.eql([]);
+
+    // past seasons are numbered up to the current one
+    const seasonResults = await program.account.seasonResult.fetchMultiple(
+      [...Array(leaderboard.season).keys()].map((season) =>
+        seasonAddress(program, gameOwner, "season_result", season)
+      )
+    );
+    expect(seasonResults).to.have.lengthOf(1);
+    expect(seasonResults[0].season).to.equal(0);
+    expect(
+      seasonResults[0].standings.map((player) => player.score.toNumber())
+    ).to.eql([100, 50]);
+    expect(seasonResults[0].prizePool.toNumber()).to.equal(
+      0.9 * LAMPORTS_PER_SOL
+    );
+
+    try {
+      await program.methods
+        .initializeLeaderboard(server.publicKey, 5)
+        .accounts({
+          leaderboard: leaderboardPublicKey,
+          firstSeasonResult: firstSeasonResultPublicKey,
+          prizeVault: prizeVaultPublicKey,
+          gameConfig: gameConfigPublicKey,
+          gameOwner: gameOwner.publicKey,
+        })
+        .signers([gameOwner])
+        .rpc();
+      expect.fail("should've failed but didn't");
+    } catch (_err) {
+      expect(_err).to.be.instanceOf(AnchorError);
+      const err: AnchorError = _err;
+      expect(err.error.errorCode.code).to.equal("SeasonEnded");
+    }
+  });
+
+  it("distributes prizes to the top players", async () => {
+    const distributePrizes = () =>
+      program.methods
+        .distributePrizes()
+        .accounts({
+          seasonResult: firstSeasonResultPublicKey,
+          prizeVault: prizeVaultPublicKey,
+          gameOwner: gameOwner.publicKey,
+          prizeVaultTokenAccount: null,
+          gameOwnerTokenAccount: null,
+          tokenProgram: null,
+        })
+        // both of the top scores are the user's, who wins one prize
+        .remainingAccounts([
+          { pubkey: user.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([gameOwner])
+        .rpc();
+    const userBalance = await connection.getBalance(user.publicKey);
+    const gameOwnerBalance = await connection.getBalance(gameOwner.publicKey);
+
+    await distributePrizes();
+
+    // the prize split is 70/30, and the unclaimed share goes to the owner
+    expect(await connection.getBalance(user.publicKey)).to.equal(
+      userBalance + (0.9 * LAMPORTS_PER_SOL * 70) / 100
+    );
+    expect(await connection.getBalance(gameOwner.publicKey)).to.equal(
+      gameOwnerBalance + (0.9 * LAMPORTS_PER_SOL * 30) / 100
+    );
+    const seasonResult = await program.account.seasonResult.fetch(
+      firstSeasonResultPublicKey
+    );
+    expect(seasonResult.prizePool.toNumber()).to.equal(0);
+
+    try {
+      await distributePrizes();
+      expect.fail("should've failed but didn't");
+    } catch (_err) {
+      expect(_err).to.be.instanceOf(AnchorError);
+      const err: AnchorError = _err;
+      expect(err.error.errorCode.code).to.equal("NoPrizes");
+    }
+  });
+
+  it("charges the entry fee in tokens", async () => {
+    const season = 1;
+    const leaderboard = seasonAddress(
+      program,
+      gameOwner,
+      "leaderboard",
+      season
+    );
+    const {
+      mint,
+      tokenAccounts: [userTokens, gameOwnerTokens, prizeVaultTokens],
+    } = await createMintAndTokenAccounts(connection, gameOwner, [
+      user.publicKey,
+      gameOwner.publicKey,
+      prizeVaultPublicKey,
+    ]);
+    await mintTo(connection, gameOwner, mint, userTokens, 1_000);
+    const tokenAccounts = {
+      userTokenAccount: userTokens,
+      gameOwnerTokenAccount: gameOwnerTokens,
+      prizeVaultTokenAccount: prizeVaultTokens,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+    const updateConfig = (entryFee: number, mint: PublicKey | null) =>
+      program.methods
+        .updateConfig(new anchor.BN(entryFee), mint)
+        .accounts({
+          gameConfig: gameConfigPublicKey,
+          leaderboard,
+          gameOwner: gameOwner.publicKey,
+        })
+        .signers([gameOwner])
+        .rpc();
+    const tokenBalance = async (address: PublicKey) =>
+      Number((await connection.getTokenAccountBalance(address)).value.amount);
+
+    await updateConfig(100, mint);
+
+    try {
+      await newGame(leaderboard);
+      expect.fail("should've failed but didn't");
+    } catch (_err) {
+      expect(_err).to.be.instanceOf(AnchorError);
+      const err: AnchorError = _err;
+      expect(err.error.errorCode.code).to.equal("MissingTokenAccounts");
+    }
+
+    // the house cut and prize split carry over from the first season
+    await newGame(leaderboard, tokenAccounts);
+
+    expect(await tokenBalance(userTokens)).to.equal(900);
+    expect(await tokenBalance(gameOwnerTokens)).to.equal(10);
+    expect(await tokenBalance(prizeVaultTokens)).to.equal(90);
+
+    try {
+      await updateConfig(LAMPORTS_PER_SOL, null);
+      expect.fail("should've failed but didn't");
+    } catch (_err) {
+      expect(_err).to.be.instanceOf(AnchorError);
+      const err: AnchorError = _err;
+      expect(err.error.errorCode.code).to.equal("PrizePoolNotEmpty");
+    }
+
+    const seasonResult = seasonAddress(
+      program,
+      gameOwner,
+      "season_result",
+      season
+    );
+    await program.methods
+      .startSeason()
+      .accounts({
+        leaderboard,
+        seasonResult,
+        nextLeaderboard: seasonAddress(
+          program,
+          gameOwner,
+          "leaderboard",
+          season + 1
+        ),
+        gameConfig: gameConfigPublicKey,
+        gameOwner: gameOwner.publicKey,
+      })
+      .signers([gameOwner])
+      .rpc();
+    // nobody recorded a score, so the pool goes to the game owner
+    await program.methods
+      .distributePrizes()
+      .accounts({
+        seasonResult,
+        prizeVault: prizeVaultPublicKey,
+        gameOwner: gameOwner.publicKey,
+        prizeVaultTokenAccount: prizeVaultTokens,
+        gameOwnerTokenAccount: gameOwnerTokens,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([gameOwner])
+      .rpc();
+
+    expect(await tokenBalance(gameOwnerTokens)).to.equal(100);
+    expect(await tokenBalance(prizeVaultTokens)).to.equal(0);
   });
 });
+
+const TOKEN_PROGRAM_ID = new PublicKey(
+  "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+);
+// The sizes of the `Mint` and `Account` states of the token program.
+const MINT_SIZE = 82;
+const TOKEN_ACCOUNT_SIZE = 165;
+
+type TokenAccounts = {
+  userTokenAccount: PublicKey | null;
+  gameOwnerTokenAccount: PublicKey | null;
+  prizeVaultTokenAccount: PublicKey | null;
+  tokenProgram: PublicKey | null;
+};
+
+const noTokenAccounts: TokenAccounts = {
+  userTokenAccount: null,
+  gameOwnerTokenAccount: null,
+  prizeVaultTokenAccount: null,
+  tokenProgram: null,
+};
+
+/**
+ * Creates a mint without decimals, with `payer` as its mint authority,
+ * and a token account of it for each of `owners`.
+ */
+async function createMintAndTokenAccounts(
+  connection: Connection,
+  payer: Keypair,
+  owners: PublicKey[]
+): Promise<{ mint: PublicKey; tokenAccounts: PublicKey[] }> {
+  const mint = Keypair.generate();
+  const tokenAccounts = owners.map(() => Keypair.generate());
+  const createAccount = async (account: Keypair, space: number) =>
+    SystemProgram.createAccount({
+      fromPubkey: payer.publicKey,
+      newAccountPubkey: account.publicKey,
+      lamports: await connection.getMinimumBalanceForRentExemption(space),
+      space,
+      programId: TOKEN_PROGRAM_ID,
+    });
+
+  const transaction = new Transaction().add(
+    await createAccount(mint, MINT_SIZE),
+    // `InitializeMint2`, without a freeze authority
+    new TransactionInstruction({
+      keys: [{ pubkey: mint.publicKey, isSigner: false, isWritable: true }],
+      programId: TOKEN_PROGRAM_ID,
+      data: Buffer.concat([
+        Buffer.from([20, 0]),
+        payer.publicKey.toBuffer(),
+        Buffer.from([0]),
+      ]),
+    })
+  );
+  for (const [i, owner] of owners.entries()) {
+    transaction.add(
+      await createAccount(tokenAccounts[i], TOKEN_ACCOUNT_SIZE),
+      // `InitializeAccount3`
+      new TransactionInstruction({
+        keys: [
+          {
+            pubkey: tokenAccounts[i].publicKey,
+            isSigner: false,
+            isWritable: true,
+          },
+          { pubkey: mint.publicKey, isSigner: false, isWritable: false },
+        ],
+        programId: TOKEN_PROGRAM_ID,
+        data: Buffer.concat([Buffer.from([18]), owner.toBuffer()]),
+      })
+    );
+  }
+  await sendAndConfirmTransaction(connection, transaction, [
+    payer,
+    mint,
+    ...tokenAccounts,
+  ]);
+
+  return {
+    mint: mint.publicKey,
+    tokenAccounts: tokenAccounts.map((account) => account.publicKey),
+  };
+}
+
+/**
+ * Mints `amount` tokens of `mint` to `destination`.
+ */
+async function mintTo(
+  connection: Connection,
+  authority: Keypair,
+  mint: PublicKey,
+  destination: PublicKey,
+  amount: number
+) {
+  // `MintTo`
+  const instruction = new TransactionInstruction({
+    keys: [
+      { pubkey: mint, isSigner: false, isWritable: true },
+      { pubkey: destination, isSigner: false, isWritable: true },
+      { pubkey: authority.publicKey, isSigner: true, isWritable: false },
+    ],
+    programId: TOKEN_PROGRAM_ID,
+    data: Buffer.concat([
+      Buffer.from([7]),
+      new anchor.BN(amount).toArrayLike(Buffer, "le", 8),
+    ]),
+  });
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(instruction),
+    [authority]
+  );
+}
+
+/**
+ * Derives the leaderboard or season result address of a season.
+ */
+function seasonAddress(
+  program: Program<RockDestroyer>,
+  gameOwner: Keypair,
+  seed: "leaderboard" | "season_result",
+  season: number
+): PublicKey {
+  const seasonSeed = Buffer.alloc(4);
+  seasonSeed.writeUInt32LE(season);
+  const [address] = PublicKey.findProgramAddressSync(
+    [Buffer.from(seed), gameOwner.publicKey.toBuffer(), seasonSeed],
+    program.programId
+  );
+  return address;
+}
+
+/**
+ * Submits a score, with the attestation signed by `authority`.
+ */
+async function addScore(
+  program: Program<RockDestroyer>,
+  authority: Keypair,
+  user: Keypair,
+  leaderboard: PublicKey,
+  gameNonce: anchor.BN,
+  score: number
+) {
+  const scoreBN = new anchor.BN(score);
+  const message = Buffer.concat([
+    user.publicKey.toBuffer(),
+    gameNonce.toArrayLike(Buffer, "le", 8),
+    scoreBN.toArrayLike(Buffer, "le", 8),
+  ]);
+
+  await program.methods
+    .addPlayerToLeaderboard(gameNonce, scoreBN)
+    .accounts({
+      leaderboard,
+      user: user.publicKey,
+      instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+    })
+    .preInstructions([
+      Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: authority.secretKey,
+        message,
+      }),
+    ])
+    .signers([user])
+    .rpc();
+}
@@ -18,6 +18,28 @@ export function tileToString(tile) {
   return '';
 }
 
+/**
+ * Expands the per-sign bitboards of a `Game` account into a 3x3 board,
+ * where tile `(row, column)` is bit `row * 3 + column`.
+ * @param {[number, number]} boards
+ * @returns {({ x: {} } | { o: {} } | null)[][]}
+ */
+export function boardFromBitboards(boards) {
+  const [x, o] = boards;
+  return [0, 1, 2].map(row =>
+    [0, 1, 2].map(column => {
+      const mask = 1 << (row * 3 + column);
+      if (x & mask) {
+        return { x: {} };
+      }
+      if (o & mask) {
+        return { o: {} };
+      }
+      return null;
+    })
+  );
+}
+
 /**
  * @param {({ x: {} } | { o: {} } | null)[][]} board
  */
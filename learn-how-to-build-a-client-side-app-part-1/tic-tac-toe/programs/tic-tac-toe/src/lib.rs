@@ -1,4 +1,5 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
 use num_derive;
 use num_traits::FromPrimitive;
 
@@ -13,25 +14,135 @@ pub mod tic_tac_toe {
         player_two_pubkey: Pubkey,
         _game_id: String,
     ) -> Result<()> {
+        require!(
+            _game_id.len() <= Game::MAXIMUM_GAME_ID_LENGTH,
+            TicTacToeError::GameIdTooLong
+        );
+
         let player_one = &ctx.accounts.player_one;
         let player_one_pubkey = player_one.key();
 
-        let game = &mut ctx.accounts.game;
+        let game = &mut ctx.accounts.game.load_init()?;
 
         game.start([player_one_pubkey, player_two_pubkey])
     }
 
-    pub fn play(ctx: Context<Play>, tile: Tile) -> Result<()> {
-        let game = &mut ctx.accounts.game;
+    pub fn register_session_key(
+        ctx: Context<RegisterSessionKey>,
+        delegate: Pubkey,
+        expires_at: i64,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game.load_mut()?;
+        let now = Clock::get()?.unix_timestamp;
 
-        require_keys_eq!(
-            game.current_player(),
+        game.register_session_key(
             ctx.accounts.player.key(),
-            TicTacToeError::NotPlayersTurn
-        );
+            SessionKey {
+                delegate,
+                expires_at,
+            },
+            now,
+        )
+    }
+
+    pub fn play(ctx: Context<Play>, tile: Tile) -> Result<()> {
+        let game = &mut ctx.accounts.game.load_mut()?;
+        let player = ctx.accounts.player.key();
+
+        // the current player may either sign themselves,
+        // or have their unexpired session key sign for them
+        match game.current_session_key() {
+            Some(session_key) if session_key.delegate == player => {
+                require!(
+                    session_key.expires_at > Clock::get()?.unix_timestamp,
+                    TicTacToeError::SessionKeyExpired
+                );
+            }
+            _ => require_keys_eq!(
+                game.current_player(),
+                player,
+                TicTacToeError::NotPlayersTurn
+            ),
+        }
 
         game.play(&tile)
     }
+
+    pub fn get_state(ctx: Context<GetGame>) -> Result<GameState> {
+        Ok(ctx.accounts.game.load()?.state())
+    }
+
+    pub fn get_board(ctx: Context<GetGame>) -> Result<[[Option<Sign>; 3]; 3]> {
+        Ok(ctx.accounts.game.load()?.board())
+    }
+
+    pub fn rematch(ctx: Context<Rematch>) -> Result<()> {
+        let game = &mut ctx.accounts.game.load_mut()?;
+
+        game.rematch([ctx.accounts.player_one.key(), ctx.accounts.player_two.key()])
+    }
+
+    pub fn initialize_lobby(_ctx: Context<InitializeLobby>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn enqueue(ctx: Context<Enqueue>) -> Result<()> {
+        let player = &ctx.accounts.player;
+        let deposit = Lobby::deposit(&Rent::get()?);
+
+        ctx.accounts.lobby.enqueue(LobbyEntry {
+            player: player.key(),
+            enqueued_at: Clock::get()?.unix_timestamp,
+            deposit,
+        })?;
+
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: player.to_account_info(),
+                    to: ctx.accounts.lobby.to_account_info(),
+                },
+            ),
+            deposit,
+        )
+    }
+
+    pub fn dequeue(ctx: Context<Dequeue>) -> Result<()> {
+        let entry = ctx.accounts.lobby.dequeue(ctx.accounts.player.key())?;
+
+        // refund the deposit made when joining the queue
+        **ctx
+            .accounts
+            .lobby
+            .to_account_info()
+            .try_borrow_mut_lamports()? -= entry.deposit;
+        **ctx.accounts.player.try_borrow_mut_lamports()? += entry.deposit;
+
+        Ok(())
+    }
+
+    pub fn match_players(ctx: Context<MatchPlayers>) -> Result<()> {
+        let lobby = &mut ctx.accounts.lobby;
+        let [player_one, player_two] = lobby.pop_oldest_pair()?;
+        lobby.games_created += 1;
+
+        ctx.accounts
+            .game
+            .load_init()?
+            .start([player_one.player, player_two.player])?;
+
+        // the deposits of the matched players pay back the rent of the game
+        let deposits = player_one.deposit + player_two.deposit;
+        **ctx
+            .accounts
+            .lobby
+            .to_account_info()
+            .try_borrow_mut_lamports()? -= deposits;
+        **ctx.accounts.payer.try_borrow_mut_lamports()? += deposits;
+
+        Ok(())
+    }
 }
 
 #[derive(Accounts)]
@@ -41,30 +152,56 @@ pub struct SetupGame<'info> {
         init,
         payer = player_one,
         space = 8 + Game::MAXIMUM_SIZE,
-        seeds = [b"game", player_one.key().as_ref(), _game_id.as_bytes()],
+        seeds = [b"game", player_one.key().as_ref(), Game::game_id_seed(&_game_id)],
         bump
     )]
-    pub game: Account<'info, Game>,
+    pub game: AccountLoader<'info, Game>,
     #[account(mut)]
     pub player_one: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
-#[account]
+/// The board is stored as one bitboard per sign,
+/// where tile `(row, column)` is bit `row * 3 + column`.
+/// Use `Game::board` and `Game::state` for the expanded representation.
+#[account(zero_copy)]
 pub struct Game {
     players: [Pubkey; 2],          // (32 * 2)
+    session_keys: [SessionKey; 2], // 2 * (32 + 8)
+    winner: Pubkey,                // 32
+    boards: [u16; 2],              // 2 * 2
     turn: u8,                      // 1
-    board: [[Option<Sign>; 3]; 3], // 9 * (1 + 1) = 18
-    state: GameState,              // 32 + 1
+    state: u8,                     // 1
+    round: u16,                    // 2
+}
+
+#[derive(Accounts)]
+pub struct RegisterSessionKey<'info> {
+    #[account(mut)]
+    pub game: AccountLoader<'info, Game>,
+    pub player: Signer<'info>,
 }
 
 #[derive(Accounts)]
 pub struct Play<'info> {
     #[account(mut)]
-    pub game: Account<'info, Game>,
+    pub game: AccountLoader<'info, Game>,
     pub player: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct GetGame<'info> {
+    pub game: AccountLoader<'info, Game>,
+}
+
+#[derive(Accounts)]
+pub struct Rematch<'info> {
+    #[account(mut)]
+    pub game: AccountLoader<'info, Game>,
+    pub player_one: Signer<'info>,
+    pub player_two: Signer<'info>,
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
 pub enum GameState {
     Active,
@@ -72,6 +209,14 @@ pub enum GameState {
     Won { winner: Pubkey },
 }
 
+/// An ephemeral key allowed to play on behalf of a player until `expires_at`.
+/// A default `delegate` means no session key is registered.
+#[zero_copy]
+pub struct SessionKey {
+    delegate: Pubkey,
+    expires_at: i64,
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize, num_derive::FromPrimitive, Copy, Clone, PartialEq)]
 pub enum Sign {
     X,
@@ -79,17 +224,82 @@ pub enum Sign {
 }
 
 impl Game {
-    pub const MAXIMUM_SIZE: usize = (32 * 2) + 1 + (9 * (1 + 1)) + (32 + 1);
+    pub const MAXIMUM_SIZE: usize = std::mem::size_of::<Game>();
+    pub const MAXIMUM_GAME_ID_LENGTH: usize = 32;
+    /// Session keys can be registered for at most one day.
+    pub const MAXIMUM_SESSION_DURATION: i64 = 60 * 60 * 24;
+
+    const ACTIVE: u8 = 0;
+    const TIE: u8 = 1;
+    const WON: u8 = 2;
+
+    const FULL_BOARD: u16 = 0b111_111_111;
+    const WINNING_LINES: [u16; 8] = [
+        // rows
+        0b000_000_111,
+        0b000_111_000,
+        0b111_000_000,
+        // columns
+        0b001_001_001,
+        0b010_010_010,
+        0b100_100_100,
+        // diagonals
+        0b100_010_001,
+        0b001_010_100,
+    ];
+
+    /// Returns the `game_id` bytes used to seed the game PDA,
+    /// capped at the maximum seed length so that deriving the address
+    /// cannot panic before `setup_game` rejects an overlong id.
+    pub fn game_id_seed(game_id: &str) -> &[u8] {
+        let bytes = game_id.as_bytes();
+        &bytes[..bytes.len().min(Self::MAXIMUM_GAME_ID_LENGTH)]
+    }
 
     pub fn start(&mut self, players: [Pubkey; 2]) -> Result<()> {
         require_eq!(self.turn, 0, TicTacToeError::GameAlreadyStarted);
+        require_keys_neq!(players[0], players[1], TicTacToeError::CannotPlaySelf);
+        require!(
+            players.iter().all(|player| *player != Pubkey::default()),
+            TicTacToeError::InvalidOpponent
+        );
+
         self.players = players;
         self.turn = 1;
         Ok(())
     }
 
     pub fn is_active(&self) -> bool {
-        self.state == GameState::Active
+        self.state == Self::ACTIVE
+    }
+
+    pub fn state(&self) -> GameState {
+        match self.state {
+            Self::TIE => GameState::Tie,
+            Self::WON => GameState::Won {
+                winner: self.winner,
+            },
+            _ => GameState::Active,
+        }
+    }
+
+    pub fn board(&self) -> [[Option<Sign>; 3]; 3] {
+        let mut board = [[None; 3]; 3];
+        for (row, tiles) in board.iter_mut().enumerate() {
+            for (column, tile) in tiles.iter_mut().enumerate() {
+                let mask = Self::tile_mask(row, column);
+                *tile = self
+                    .boards
+                    .iter()
+                    .position(|bitboard| bitboard & mask != 0)
+                    .map(|index| Sign::from_usize(index).unwrap());
+            }
+        }
+        board
+    }
+
+    fn tile_mask(row: usize, column: usize) -> u16 {
+        1 << (row * 3 + column)
     }
 
     fn current_player_index(&self) -> usize {
@@ -100,6 +310,35 @@ impl Game {
         self.players[self.current_player_index()]
     }
 
+    pub fn current_session_key(&self) -> Option<SessionKey> {
+        let session_key = self.session_keys[self.current_player_index()];
+        (session_key.delegate != Pubkey::default()).then_some(session_key)
+    }
+
+    pub fn register_session_key(
+        &mut self,
+        player: Pubkey,
+        session_key: SessionKey,
+        now: i64,
+    ) -> Result<()> {
+        require!(self.is_active(), TicTacToeError::GameAlreadyOver);
+
+        let player_index = self
+            .players
+            .iter()
+            .position(|p| *p == player)
+            .ok_or(TicTacToeError::NotAPlayer)?;
+
+        require!(
+            session_key.expires_at > now
+                && session_key.expires_at <= now + Self::MAXIMUM_SESSION_DURATION,
+            TicTacToeError::InvalidSessionExpiry
+        );
+
+        self.session_keys[player_index] = session_key;
+        Ok(())
+    }
+
     pub fn play(&mut self, tile: &Tile) -> Result<()> {
         require!(self.is_active(), TicTacToeError::GameAlreadyOver);
 
@@ -107,74 +346,171 @@ impl Game {
             tile @ Tile {
                 row: 0..=2,
                 column: 0..=2,
-            } => match self.board[tile.row as usize][tile.column as usize] {
-                Some(_) => return Err(TicTacToeError::TileAlreadySet.into()),
-                None => {
-                    self.board[tile.row as usize][tile.column as usize] =
-                        Some(Sign::from_usize(self.current_player_index()).unwrap());
+            } => {
+                let mask = Self::tile_mask(tile.row as usize, tile.column as usize);
+                if (self.boards[0] | self.boards[1]) & mask != 0 {
+                    return Err(TicTacToeError::TileAlreadySet.into());
                 }
-            },
+                self.boards[self.current_player_index()] |= mask;
+            }
             _ => return Err(TicTacToeError::TileOutOfBounds.into()),
         }
 
         self.update_state();
 
-        if GameState::Active == self.state {
+        if self.is_active() {
             self.turn += 1;
         }
 
         Ok(())
     }
 
-    fn is_winning_trio(&self, trio: [(usize, usize); 3]) -> bool {
-        let [first, second, third] = trio;
-        self.board[first.0][first.1].is_some()
-            && self.board[first.0][first.1] == self.board[second.0][second.1]
-            && self.board[first.0][first.1] == self.board[third.0][third.1]
+    /// Resets a finished game for another round between the same players,
+    /// with the player who moved second last round moving first.
+    pub fn rematch(&mut self, signers: [Pubkey; 2]) -> Result<()> {
+        require!(!self.is_active(), TicTacToeError::GameNotOver);
+        require!(
+            self.players.iter().all(|player| signers.contains(player)),
+            TicTacToeError::NotAPlayer
+        );
+
+        self.players.swap(0, 1);
+        self.session_keys.swap(0, 1);
+        self.boards = [0; 2];
+        self.winner = Pubkey::default();
+        self.state = Self::ACTIVE;
+        self.turn = 1;
+        self.round += 1;
+        Ok(())
     }
 
     fn update_state(&mut self) {
-        for i in 0..=2 {
-            // three of the same in one row
-            if self.is_winning_trio([(i, 0), (i, 1), (i, 2)]) {
-                self.state = GameState::Won {
-                    winner: self.current_player(),
-                };
-                return;
-            }
-            // three of the same in one column
-            if self.is_winning_trio([(0, i), (1, i), (2, i)]) {
-                self.state = GameState::Won {
-                    winner: self.current_player(),
-                };
-                return;
-            }
-        }
-
-        // three of the same in one diagonal
-        if self.is_winning_trio([(0, 0), (1, 1), (2, 2)])
-            || self.is_winning_trio([(0, 2), (1, 1), (2, 0)])
+        // only the player who just moved can have completed a line
+        let bitboard = self.boards[self.current_player_index()];
+        if Self::WINNING_LINES
+            .iter()
+            .any(|line| bitboard & line == *line)
         {
-            self.state = GameState::Won {
-                winner: self.current_player(),
-            };
+            self.state = Self::WON;
+            self.winner = self.current_player();
             return;
         }
 
-        // reaching this code means the game has not been won,
-        // so if there are unfilled tiles left, it's still active
-        for row in 0..=2 {
-            for column in 0..=2 {
-                if self.board[row][column].is_none() {
-                    return;
-                }
-            }
-        }
-
         // game has not been won
         // game has no more free tiles
         // -> game ends in a tie
-        self.state = GameState::Tie;
+        if self.boards[0] | self.boards[1] == Self::FULL_BOARD {
+            self.state = Self::TIE;
+        }
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeLobby<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Lobby::MAXIMUM_SIZE,
+        seeds = [b"lobby"],
+        bump
+    )]
+    pub lobby: Account<'info, Lobby>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Enqueue<'info> {
+    #[account(mut, seeds = [b"lobby"], bump)]
+    pub lobby: Account<'info, Lobby>,
+    #[account(mut)]
+    pub player: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Dequeue<'info> {
+    #[account(mut, seeds = [b"lobby"], bump)]
+    pub lobby: Account<'info, Lobby>,
+    #[account(mut)]
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MatchPlayers<'info> {
+    #[account(mut, seeds = [b"lobby"], bump)]
+    pub lobby: Account<'info, Lobby>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Game::MAXIMUM_SIZE,
+        seeds = [b"game", lobby.key().as_ref(), lobby.games_created.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub game: AccountLoader<'info, Game>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+pub struct Lobby {
+    games_created: u64,     // 8
+    queue: Vec<LobbyEntry>, // 4 + (MAXIMUM_QUEUE_SIZE * 48)
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq)]
+pub struct LobbyEntry {
+    player: Pubkey,   // 32
+    enqueued_at: i64, // 8
+    deposit: u64,     // 8
+}
+
+impl Lobby {
+    pub const MAXIMUM_QUEUE_SIZE: usize = 32;
+    pub const MAXIMUM_SIZE: usize = 8 + (4 + Self::MAXIMUM_QUEUE_SIZE * (32 + 8 + 8));
+
+    /// Each waiting player deposits half of the rent of the game they will be matched into.
+    pub fn deposit(rent: &Rent) -> u64 {
+        let game_rent = rent.minimum_balance(8 + Game::MAXIMUM_SIZE);
+        (game_rent + 1) / 2
+    }
+
+    pub fn enqueue(&mut self, entry: LobbyEntry) -> Result<()> {
+        require!(
+            self.queue.len() < Self::MAXIMUM_QUEUE_SIZE,
+            TicTacToeError::LobbyFull
+        );
+        require!(
+            !self.queue.iter().any(|e| e.player == entry.player),
+            TicTacToeError::AlreadyInLobby
+        );
+
+        self.queue.push(entry);
+        Ok(())
+    }
+
+    pub fn dequeue(&mut self, player: Pubkey) -> Result<LobbyEntry> {
+        let index = self
+            .queue
+            .iter()
+            .position(|e| e.player == player)
+            .ok_or(TicTacToeError::NotInLobby)?;
+
+        Ok(self.queue.remove(index))
+    }
+
+    /// The queue is kept in the order players joined,
+    /// so the two oldest waiting players are at the front.
+    pub fn pop_oldest_pair(&mut self) -> Result<[LobbyEntry; 2]> {
+        require!(
+            self.queue.len() >= 2,
+            TicTacToeError::NotEnoughPlayersInLobby
+        );
+
+        let mut pair = self.queue.drain(..2);
+        Ok([pair.next().unwrap(), pair.next().unwrap()])
     }
 }
 
@@ -191,4 +527,15 @@ pub enum TicTacToeError {
     GameAlreadyOver,
     NotPlayersTurn,
     GameAlreadyStarted,
+    NotAPlayer,
+    InvalidSessionExpiry,
+    SessionKeyExpired,
+    LobbyFull,
+    AlreadyInLobby,
+    NotInLobby,
+    NotEnoughPlayersInLobby,
+    GameNotOver,
+    CannotPlaySelf,
+    InvalidOpponent,
+    GameIdTooLong,
 }
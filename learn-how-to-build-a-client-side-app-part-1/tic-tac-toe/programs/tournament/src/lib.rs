@@ -0,0 +1,332 @@
+use anchor_lang::prelude::*;
+use tic_tac_toe::program::TicTacToe;
+use tic_tac_toe::GameState;
+
+declare_id!("Fi1qJUfW5k2tucjYuoASShQm4SN9xM7PgfiyXZFQfQYt");
+
+#[program]
+pub mod tournament {
+    use super::*;
+
+    pub fn create_tournament(
+        ctx: Context<CreateTournament>,
+        _tournament_id: String,
+        max_players: u8,
+    ) -> Result<()> {
+        let tournament = &mut ctx.accounts.tournament;
+
+        tournament.create(ctx.accounts.organizer.key(), max_players)
+    }
+
+    pub fn register(ctx: Context<Register>) -> Result<()> {
+        let tournament = &mut ctx.accounts.tournament;
+
+        tournament.register(ctx.accounts.player.key())
+    }
+
+    pub fn start_tournament(ctx: Context<StartTournament>) -> Result<()> {
+        let tournament = &mut ctx.accounts.tournament;
+
+        tournament.start()
+    }
+
+    pub fn start_match(ctx: Context<StartMatch>, match_index: u8) -> Result<()> {
+        let tournament = &mut ctx.accounts.tournament;
+        let player = ctx.accounts.player.key();
+
+        let opponent = tournament.opponent(match_index as usize, player)?;
+        let game_id = tournament.game_id(tournament.key(), match_index as usize);
+
+        tic_tac_toe::cpi::setup_game(
+            CpiContext::new(
+                ctx.accounts.tic_tac_toe_program.to_account_info(),
+                tic_tac_toe::cpi::accounts::SetupGame {
+                    game: ctx.accounts.game.to_account_info(),
+                    player_one: ctx.accounts.player.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                },
+            ),
+            opponent,
+            game_id,
+        )?;
+
+        tournament.record_game(match_index as usize, ctx.accounts.game.key());
+        Ok(())
+    }
+
+    /// Records the winner of a finished match. A tie leaves the match
+    /// undecided: its players replay the same game with
+    /// `tic_tac_toe::rematch` until it has a winner.
+    pub fn advance(ctx: Context<Advance>, match_index: u8) -> Result<()> {
+        let tournament = &mut ctx.accounts.tournament;
+
+        require!(
+            tournament.game_of(match_index as usize)? == Some(ctx.accounts.game.key()),
+            TournamentError::WrongGame
+        );
+
+        let state = tic_tac_toe::cpi::get_state(CpiContext::new(
+            ctx.accounts.tic_tac_toe_program.to_account_info(),
+            tic_tac_toe::cpi::accounts::GetGame {
+                game: ctx.accounts.game.to_account_info(),
+            },
+        ))?
+        .get();
+
+        match state {
+            GameState::Won { winner } => tournament.record_winner(match_index as usize, winner),
+            _ => Err(TournamentError::MatchNotFinished.into()),
+        }
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(_tournament_id: String, max_players: u8)]
+pub struct CreateTournament<'info> {
+    #[account(
+        init,
+        payer = organizer,
+        space = 8 + Tournament::size(max_players as usize),
+        seeds = [b"tournament", organizer.key().as_ref(), _tournament_id.as_bytes()],
+        bump
+    )]
+    pub tournament: Account<'info, Tournament>,
+    #[account(mut)]
+    pub organizer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Register<'info> {
+    #[account(mut)]
+    pub tournament: Account<'info, Tournament>,
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct StartTournament<'info> {
+    #[account(mut, has_one = organizer)]
+    pub tournament: Account<'info, Tournament>,
+    pub organizer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct StartMatch<'info> {
+    #[account(mut)]
+    pub tournament: Account<'info, Tournament>,
+    /// CHECK: initialized by the `tic_tac_toe` program
+    #[account(mut)]
+    pub game: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub player: Signer<'info>,
+    pub tic_tac_toe_program: Program<'info, TicTacToe>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Advance<'info> {
+    #[account(mut)]
+    pub tournament: Account<'info, Tournament>,
+    /// CHECK: read through `tic_tac_toe::get_state`
+    pub game: UncheckedAccount<'info>,
+    pub tic_tac_toe_program: Program<'info, TicTacToe>,
+}
+
+#[account]
+pub struct Tournament {
+    organizer: Pubkey,            // 32
+    max_players: u8,              // 1
+    round: u8,                    // 1
+    state: TournamentState,       // 1 + 32
+    players: Vec<Pubkey>,         // 4 + (max_players * 32)
+    bracket: Vec<Option<Pubkey>>, // 4 + (slots * (1 + 32))
+    games: Vec<Option<Pubkey>>,   // 4 + (slots / 2 * (1 + 32))
+    winners: Vec<Option<Pubkey>>, // 4 + (slots / 2 * (1 + 32))
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub enum TournamentState {
+    Registration,
+    InProgress,
+    Finished { champion: Pubkey },
+}
+
+impl Tournament {
+    pub const MAXIMUM_PLAYERS: usize = 16;
+
+    /// The bracket is padded with byes up to `slots`,
+    /// the next power of two from `max_players`.
+    pub fn size(max_players: usize) -> usize {
+        let slots = max_players.next_power_of_two();
+
+        32 + 1
+            + 1
+            + (1 + 32)
+            + (4 + max_players * 32)
+            + (4 + slots * (1 + 32))
+            + 2 * (4 + slots / 2 * (1 + 32))
+    }
+
+    pub fn create(&mut self, organizer: Pubkey, max_players: u8) -> Result<()> {
+        require!(
+            (2..=Self::MAXIMUM_PLAYERS).contains(&(max_players as usize)),
+            TournamentError::InvalidMaxPlayers
+        );
+
+        self.organizer = organizer;
+        self.max_players = max_players;
+        self.state = TournamentState::Registration;
+        Ok(())
+    }
+
+    pub fn register(&mut self, player: Pubkey) -> Result<()> {
+        require!(
+            self.state == TournamentState::Registration,
+            TournamentError::RegistrationClosed
+        );
+        require!(
+            self.players.len() < self.max_players as usize,
+            TournamentError::TournamentFull
+        );
+        require!(
+            !self.players.contains(&player),
+            TournamentError::AlreadyRegistered
+        );
+
+        self.players.push(player);
+        Ok(())
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        require!(
+            self.state == TournamentState::Registration,
+            TournamentError::RegistrationClosed
+        );
+        require!(self.players.len() >= 2, TournamentError::NotEnoughPlayers);
+
+        // players are seeded in the order they registered
+        let bracket = Self::seed_order(self.players.len().next_power_of_two())
+            .into_iter()
+            .map(|seed| self.players.get(seed - 1).copied())
+            .collect();
+
+        self.state = TournamentState::InProgress;
+        self.start_round(bracket);
+        Ok(())
+    }
+
+    /// Returns the seeds in bracket order, so that the sum of the seeds
+    /// in every match is `size + 1`, and the top seeds meet last.
+    fn seed_order(size: usize) -> Vec<usize> {
+        let mut order = vec![1];
+        while order.len() < size {
+            let round_size = order.len() * 2;
+            order = order
+                .into_iter()
+                .flat_map(|seed| [seed, round_size + 1 - seed])
+                .collect();
+        }
+        order
+    }
+
+    fn start_round(&mut self, bracket: Vec<Option<Pubkey>>) {
+        let matches = bracket.len() / 2;
+
+        self.round += 1;
+        self.games = vec![None; matches];
+        // a player without an opponent gets a bye
+        self.winners = bracket
+            .chunks(2)
+            .map(|pair| match pair {
+                [Some(player), None] | [None, Some(player)] => Some(*player),
+                _ => None,
+            })
+            .collect();
+        self.bracket = bracket;
+    }
+
+    fn match_players(&self, match_index: usize) -> Result<[Option<Pubkey>; 2]> {
+        require!(
+            self.state == TournamentState::InProgress,
+            TournamentError::TournamentNotInProgress
+        );
+        require!(
+            match_index < self.winners.len(),
+            TournamentError::InvalidMatch
+        );
+        require!(
+            self.winners[match_index].is_none(),
+            TournamentError::MatchAlreadyDecided
+        );
+
+        Ok([
+            self.bracket[match_index * 2],
+            self.bracket[match_index * 2 + 1],
+        ])
+    }
+
+    pub fn opponent(&self, match_index: usize, player: Pubkey) -> Result<Pubkey> {
+        let players = self.match_players(match_index)?;
+        require!(
+            self.games[match_index].is_none(),
+            TournamentError::MatchAlreadyStarted
+        );
+
+        match players {
+            [Some(one), Some(two)] if one == player => Ok(two),
+            [Some(one), Some(two)] if two == player => Ok(one),
+            _ => Err(TournamentError::NotInMatch.into()),
+        }
+    }
+
+    /// Game ids are unique per tournament, round and match,
+    /// and short enough to be used as a PDA seed by `tic_tac_toe`.
+    pub fn game_id(&self, tournament: Pubkey, match_index: usize) -> String {
+        let prefix: String = tournament.to_bytes()[..8]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!("{}-{}-{}", prefix, self.round, match_index)
+    }
+
+    pub fn game_of(&self, match_index: usize) -> Result<Option<Pubkey>> {
+        self.match_players(match_index)?;
+        Ok(self.games[match_index])
+    }
+
+    pub fn record_game(&mut self, match_index: usize, game: Pubkey) {
+        self.games[match_index] = Some(game);
+    }
+
+    pub fn record_winner(&mut self, match_index: usize, winner: Pubkey) -> Result<()> {
+        self.winners[match_index] = Some(winner);
+
+        if self.winners.iter().any(Option::is_none) {
+            return Ok(());
+        }
+
+        if self.winners.len() == 1 {
+            self.state = TournamentState::Finished { champion: winner };
+        } else {
+            let bracket = self.winners.clone();
+            self.start_round(bracket);
+        }
+        Ok(())
+    }
+}
+
+#[error_code]
+pub enum TournamentError {
+    InvalidMaxPlayers,
+    RegistrationClosed,
+    TournamentFull,
+    AlreadyRegistered,
+    NotEnoughPlayers,
+    TournamentNotInProgress,
+    InvalidMatch,
+    MatchAlreadyDecided,
+    MatchAlreadyStarted,
+    NotInMatch,
+    WrongGame,
+    MatchNotFinished,
+}
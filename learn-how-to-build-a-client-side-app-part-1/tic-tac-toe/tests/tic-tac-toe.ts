@@ -1,16 +1,659 @@
-import * as anchor from "@coral-xyz/anchor";
-import { Program } from "@coral-xyz/anchor";
-import { TicTacToe } from "../target/types/tic_tac_toe";
+import {
+  AnchorError,
+  Program,
+  AnchorProvider,
+  setProvider,
+  workspace,
+  BN
+} from '@coral-xyz/anchor';
+import { TicTacToe } from '../target/types/tic_tac_toe';
+import { TicTacToeBorsh } from '../target/types/tic_tac_toe_borsh';
+import chai, { expect } from 'chai';
+import { Keypair, PublicKey } from '@solana/web3.js';
 
-describe("tic-tac-toe", () => {
+describe('tic-tac-toe', () => {
   // Configure the client to use the local cluster.
-  anchor.setProvider(anchor.AnchorProvider.env());
+  setProvider(AnchorProvider.env());
 
-  const program = anchor.workspace.TicTacToe as Program<TicTacToe>;
+  const program = workspace.TicTacToe as Program<TicTacToe>;
+  const borshProgram = workspace.TicTacToeBorsh as Program<TicTacToeBorsh>;
+  const programProvider = program.provider as AnchorProvider;
 
-  it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods.initialize().rpc();
-    console.log("Your transaction signature", tx);
+  it('initializes a game', async () => {
+    const playerOne = Keypair.generate();
+    const playerTwo = Keypair.generate();
+
+    const gamePublicKey = await setupGame(
+      program,
+      programProvider,
+      playerOne,
+      playerTwo,
+      'game-1'
+    );
+
+    const gameData = await program.account.game.fetch(gamePublicKey);
+
+    expect(gameData.turn).to.equal(1);
+    expect(gameData.players).to.eql([playerOne.publicKey, playerTwo.publicKey]);
+
+    expect(decodeState(gameData)).to.eql({ active: {} });
+    expect(decodeBoard(gameData.boards)).to.eql([
+      [null, null, null],
+      [null, null, null],
+      [null, null, null]
+    ]);
+  });
+
+  it('rejects invalid setup arguments', async () => {
+    const playerOne = Keypair.generate();
+    await airdrop(programProvider, playerOne.publicKey);
+
+    const cases: Array<[PublicKey, string, string]> = [
+      [playerOne.publicKey, 'self', 'CannotPlaySelf'],
+      [PublicKey.default, 'default', 'InvalidOpponent'],
+      [Keypair.generate().publicKey, 'x'.repeat(33), 'GameIdTooLong']
+    ];
+    for (const [playerTwoPublicKey, gameId, code] of cases) {
+      // the program seeds the game with at most 32 bytes of the game id
+      const [gamePublicKey, _] = PublicKey.findProgramAddressSync(
+        [
+          Buffer.from('game'),
+          playerOne.publicKey.toBuffer(),
+          Buffer.from(gameId).subarray(0, 32)
+        ],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .setupGame(playerTwoPublicKey, gameId)
+          .accounts({
+            game: gamePublicKey,
+            playerOne: playerOne.publicKey
+          })
+          .signers([playerOne])
+          .rpc();
+        chai.assert(false, "should've failed but didn't ");
+      } catch (_err) {
+        expect(_err).to.be.instanceOf(AnchorError);
+        const err: AnchorError = _err;
+        expect(err.error.errorCode.code).to.equal(code);
+      }
+    }
+  });
+
+  it('lets a session key play on behalf of a player', async () => {
+    const playerOne = Keypair.generate();
+    const playerTwo = Keypair.generate();
+    const sessionKey = Keypair.generate();
+
+    const gamePublicKey = await setupGame(
+      program,
+      programProvider,
+      playerOne,
+      playerTwo,
+      'session-key'
+    );
+
+    await program.methods
+      .registerSessionKey(sessionKey.publicKey, new BN(nowInSeconds() + 3600))
+      .accounts({
+        game: gamePublicKey,
+        player: playerOne.publicKey
+      })
+      .signers([playerOne])
+      .rpc();
+
+    const gameData = await program.account.game.fetch(gamePublicKey);
+    expect(gameData.sessionKeys[0].delegate).to.eql(sessionKey.publicKey);
+    expect(gameData.sessionKeys[1].delegate).to.eql(PublicKey.default);
+
+    await play(
+      program,
+      gamePublicKey,
+      sessionKey,
+      { row: 0, column: 0 },
+      2,
+      { active: {} },
+      [
+        [{ x: {} }, null, null],
+        [null, null, null],
+        [null, null, null]
+      ]
+    );
+
+    // the session key only acts for player one
+    try {
+      await play(
+        program,
+        gamePublicKey,
+        sessionKey,
+        { row: 1, column: 1 },
+        3,
+        { active: {} },
+        [
+          [{ x: {} }, null, null],
+          [null, { o: {} }, null],
+          [null, null, null]
+        ]
+      );
+      chai.assert(false, "should've failed but didn't ");
+    } catch (_err) {
+      expect(_err).to.be.instanceOf(AnchorError);
+      const err: AnchorError = _err;
+      expect(err.error.errorCode.code).to.equal('NotPlayersTurn');
+    }
+
+    await play(
+      program,
+      gamePublicKey,
+      playerTwo,
+      { row: 1, column: 1 },
+      3,
+      { active: {} },
+      [
+        [{ x: {} }, null, null],
+        [null, { o: {} }, null],
+        [null, null, null]
+      ]
+    );
+
+    // player one can still sign for themselves
+    await play(
+      program,
+      gamePublicKey,
+      playerOne,
+      { row: 0, column: 1 },
+      4,
+      { active: {} },
+      [
+        [{ x: {} }, { x: {} }, null],
+        [null, { o: {} }, null],
+        [null, null, null]
+      ]
+    );
+  });
+
+  it('rejects invalid session keys', async () => {
+    const playerOne = Keypair.generate();
+    const playerTwo = Keypair.generate();
+    const stranger = Keypair.generate();
+    const sessionKey = Keypair.generate();
+
+    const gamePublicKey = await setupGame(
+      program,
+      programProvider,
+      playerOne,
+      playerTwo,
+      'invalid-session-key'
+    );
+
+    try {
+      await program.methods
+        .registerSessionKey(sessionKey.publicKey, new BN(nowInSeconds() + 3600))
+        .accounts({
+          game: gamePublicKey,
+          player: stranger.publicKey
+        })
+        .signers([stranger])
+        .rpc();
+      chai.assert(false, "should've failed but didn't ");
+    } catch (_err) {
+      expect(_err).to.be.instanceOf(AnchorError);
+      const err: AnchorError = _err;
+      expect(err.error.errorCode.code).to.equal('NotAPlayer');
+    }
+
+    for (const expiresAt of [
+      nowInSeconds() - 60,
+      nowInSeconds() + 60 * 60 * 24 * 7
+    ]) {
+      try {
+        await program.methods
+          .registerSessionKey(sessionKey.publicKey, new BN(expiresAt))
+          .accounts({
+            game: gamePublicKey,
+            player: playerOne.publicKey
+          })
+          .signers([playerOne])
+          .rpc();
+        chai.assert(false, "should've failed but didn't ");
+      } catch (_err) {
+        expect(_err).to.be.instanceOf(AnchorError);
+        const err: AnchorError = _err;
+        expect(err.error.errorCode.code).to.equal('InvalidSessionExpiry');
+      }
+    }
+  });
+
+  it('plays a full game in fewer compute units than Borsh', async () => {
+    const playerOne = Keypair.generate();
+    const playerTwo = Keypair.generate();
+
+    const gamePublicKey = await setupGame(
+      program,
+      programProvider,
+      playerOne,
+      playerTwo,
+      'benchmark'
+    );
+    // the same game, with the board stored as `[[Option<Sign>; 3]; 3]`
+    const [borshGamePublicKey] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('game'),
+        playerOne.publicKey.toBuffer(),
+        Buffer.from('benchmark')
+      ],
+      borshProgram.programId
+    );
+    await borshProgram.methods
+      .setupGame(playerTwo.publicKey, 'benchmark')
+      .accounts({ game: borshGamePublicKey, playerOne: playerOne.publicKey })
+      .signers([playerOne])
+      .rpc();
+
+    // the game only ends, in a tie, once every tile is filled
+    const moves: Array<[Keypair, { row: number; column: number }]> = [
+      [playerOne, { row: 0, column: 0 }],
+      [playerTwo, { row: 0, column: 1 }],
+      [playerOne, { row: 0, column: 2 }],
+      [playerTwo, { row: 1, column: 1 }],
+      [playerOne, { row: 1, column: 0 }],
+      [playerTwo, { row: 1, column: 2 }],
+      [playerOne, { row: 2, column: 1 }],
+      [playerTwo, { row: 2, column: 0 }],
+      [playerOne, { row: 2, column: 2 }]
+    ];
+
+    const computeUnits: number[] = [];
+    const borshComputeUnits: number[] = [];
+    for (const [player, tile] of moves) {
+      const signature = await program.methods
+        .play(tile)
+        .accounts({ player: player.publicKey, game: gamePublicKey })
+        .signers([player])
+        .rpc({ commitment: 'confirmed' });
+      computeUnits.push(await consumedComputeUnits(programProvider, signature));
+
+      const borshSignature = await borshProgram.methods
+        .play(tile)
+        .accounts({ player: player.publicKey, game: borshGamePublicKey })
+        .signers([player])
+        .rpc({ commitment: 'confirmed' });
+      borshComputeUnits.push(
+        await consumedComputeUnits(programProvider, borshSignature)
+      );
+    }
+
+    const gameData = await program.account.game.fetch(gamePublicKey);
+    expect(decodeState(gameData)).to.eql({ tie: {} });
+    const borshGameData = await borshProgram.account.game.fetch(
+      borshGamePublicKey
+    );
+    expect(borshGameData.state).to.eql({ tie: {} });
+    expect(decodeBoard(gameData.boards)).to.eql(borshGameData.board);
+
+    for (const units of computeUnits) {
+      expect(units).to.be.below(PLAY_COMPUTE_BUDGET);
+    }
+    const total = (units: number[]) => units.reduce((sum, u) => sum + u, 0);
+    expect(total(computeUnits)).to.be.below(total(borshComputeUnits));
+  });
+
+  it('returns the state and board through views', async () => {
+    const playerOne = Keypair.generate();
+    const playerTwo = Keypair.generate();
+
+    const gamePublicKey = await setupGame(
+      program,
+      programProvider,
+      playerOne,
+      playerTwo,
+      'views'
+    );
+
+    expect(
+      await program.methods.getState().accounts({ game: gamePublicKey }).view()
+    ).to.eql({ active: {} });
+
+    await play(
+      program,
+      gamePublicKey,
+      playerOne,
+      { row: 2, column: 1 },
+      2,
+      { active: {} },
+      [
+        [null, null, null],
+        [null, null, null],
+        [null, { x: {} }, null]
+      ]
+    );
+
+    expect(
+      await program.methods.getBoard().accounts({ game: gamePublicKey }).view()
+    ).to.eql([
+      [null, null, null],
+      [null, null, null],
+      [null, { x: {} }, null]
+    ]);
+  });
+
+  it('rematches on the same game account', async () => {
+    const playerOne = Keypair.generate();
+    const playerTwo = Keypair.generate();
+
+    const gamePublicKey = await setupGame(
+      program,
+      programProvider,
+      playerOne,
+      playerTwo,
+      'rematch'
+    );
+
+    try {
+      await rematch(program, gamePublicKey, playerOne, playerTwo);
+      chai.assert(false, "should've failed but didn't ");
+    } catch (_err) {
+      expect(_err).to.be.instanceOf(AnchorError);
+      const err: AnchorError = _err;
+      expect(err.error.errorCode.code).to.equal('GameNotOver');
+    }
+
+    const moves: Array<[Keypair, { row: number; column: number }]> = [
+      [playerOne, { row: 0, column: 0 }],
+      [playerTwo, { row: 1, column: 0 }],
+      [playerOne, { row: 0, column: 1 }],
+      [playerTwo, { row: 1, column: 1 }],
+      [playerOne, { row: 0, column: 2 }]
+    ];
+    for (const [player, tile] of moves) {
+      await program.methods
+        .play(tile)
+        .accounts({ player: player.publicKey, game: gamePublicKey })
+        .signers([player])
+        .rpc();
+    }
+
+    await rematch(program, gamePublicKey, playerOne, playerTwo);
+
+    const gameData = await program.account.game.fetch(gamePublicKey);
+    expect(gameData.round).to.equal(1);
+    expect(gameData.turn).to.equal(1);
+    expect(gameData.players).to.eql([playerTwo.publicKey, playerOne.publicKey]);
+    expect(decodeState(gameData)).to.eql({ active: {} });
+    expect(decodeBoard(gameData.boards)).to.eql([
+      [null, null, null],
+      [null, null, null],
+      [null, null, null]
+    ]);
+
+    // player two moves first in the rematch
+    await play(
+      program,
+      gamePublicKey,
+      playerTwo,
+      { row: 1, column: 1 },
+      2,
+      { active: {} },
+      [
+        [null, null, null],
+        [null, { x: {} }, null],
+        [null, null, null]
+      ]
+    );
+  });
+
+  it('matches the two oldest players in the lobby', async () => {
+    const [lobbyPublicKey, _] = PublicKey.findProgramAddressSync(
+      [Buffer.from('lobby')],
+      program.programId
+    );
+
+    await program.methods
+      .initializeLobby()
+      .accounts({
+        lobby: lobbyPublicKey,
+        payer: programProvider.publicKey
+      })
+      .rpc();
+
+    const players = [
+      Keypair.generate(),
+      Keypair.generate(),
+      Keypair.generate()
+    ];
+    for (const player of players) {
+      await airdrop(programProvider, player.publicKey);
+      await program.methods
+        .enqueue()
+        .accounts({ lobby: lobbyPublicKey, player: player.publicKey })
+        .signers([player])
+        .rpc();
+    }
+
+    try {
+      await program.methods
+        .enqueue()
+        .accounts({ lobby: lobbyPublicKey, player: players[0].publicKey })
+        .signers([players[0]])
+        .rpc();
+      chai.assert(false, "should've failed but didn't ");
+    } catch (_err) {
+      expect(_err).to.be.instanceOf(AnchorError);
+      const err: AnchorError = _err;
+      expect(err.error.errorCode.code).to.equal('AlreadyInLobby');
+    }
+
+    let lobbyData = await program.account.lobby.fetch(lobbyPublicKey);
+    expect(lobbyData.queue.map(entry => entry.player)).to.eql(
+      players.map(player => player.publicKey)
+    );
+
+    // the last player leaves the queue and gets their deposit back
+    const leavingPlayer = players[2];
+    const deposit = lobbyData.queue[2].deposit.toNumber();
+    const balanceBefore = await programProvider.connection.getBalance(
+      leavingPlayer.publicKey
+    );
+    await program.methods
+      .dequeue()
+      .accounts({ lobby: lobbyPublicKey, player: leavingPlayer.publicKey })
+      .signers([leavingPlayer])
+      .rpc();
+    const balanceAfter = await programProvider.connection.getBalance(
+      leavingPlayer.publicKey
+    );
+    expect(balanceAfter).to.equal(balanceBefore + deposit);
+
+    lobbyData = await program.account.lobby.fetch(lobbyPublicKey);
+    expect(lobbyData.queue).to.have.lengthOf(2);
+
+    const [gamePublicKey] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('game'),
+        lobbyPublicKey.toBuffer(),
+        lobbyData.gamesCreated.toArrayLike(Buffer, 'le', 8)
+      ],
+      program.programId
+    );
+    await program.methods
+      .matchPlayers()
+      .accounts({
+        lobby: lobbyPublicKey,
+        game: gamePublicKey,
+        payer: programProvider.publicKey
+      })
+      .rpc();
+
+    const gameData = await program.account.game.fetch(gamePublicKey);
+    expect(gameData.turn).to.equal(1);
+    expect(gameData.players).to.eql([
+      players[0].publicKey,
+      players[1].publicKey
+    ]);
+
+    lobbyData = await program.account.lobby.fetch(lobbyPublicKey);
+    expect(lobbyData.queue).to.be.empty;
+    expect(lobbyData.gamesCreated.toNumber()).to.equal(1);
+
+    const [nextGamePublicKey] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('game'),
+        lobbyPublicKey.toBuffer(),
+        lobbyData.gamesCreated.toArrayLike(Buffer, 'le', 8)
+      ],
+      program.programId
+    );
+    try {
+      await program.methods
+        .matchPlayers()
+        .accounts({
+          lobby: lobbyPublicKey,
+          game: nextGamePublicKey,
+          payer: programProvider.publicKey
+        })
+        .rpc();
+      chai.assert(false, "should've failed but didn't ");
+    } catch (_err) {
+      expect(_err).to.be.instanceOf(AnchorError);
+      const err: AnchorError = _err;
+      expect(err.error.errorCode.code).to.equal('NotEnoughPlayersInLobby');
+    }
   });
 });
+
+// Upper bound on the compute units of a single `play`.
+// Update alongside any change to the `Game` layout.
+const PLAY_COMPUTE_BUDGET = 10_000;
+
+function nowInSeconds(): number {
+  return Math.floor(Date.now() / 1000);
+}
+
+async function consumedComputeUnits(
+  programProvider: AnchorProvider,
+  signature: string
+): Promise<number> {
+  const tx = await programProvider.connection.getTransaction(signature, {
+    commitment: 'confirmed',
+    maxSupportedTransactionVersion: 0
+  });
+  return tx.meta.computeUnitsConsumed;
+}
+
+async function airdrop(programProvider: AnchorProvider, publicKey: PublicKey) {
+  const sg = await programProvider.connection.requestAirdrop(
+    publicKey,
+    1_000_000_000
+  );
+  await programProvider.connection.confirmTransaction(sg);
+}
+
+async function rematch(
+  program: Program<TicTacToe>,
+  game: PublicKey,
+  playerOne: Keypair,
+  playerTwo: Keypair
+) {
+  await program.methods
+    .rematch()
+    .accounts({
+      game,
+      playerOne: playerOne.publicKey,
+      playerTwo: playerTwo.publicKey
+    })
+    .signers([playerOne, playerTwo])
+    .rpc();
+}
+
+async function setupGame(
+  program: Program<TicTacToe>,
+  programProvider: AnchorProvider,
+  playerOne: Keypair,
+  playerTwo: Keypair,
+  gameId: string
+): Promise<PublicKey> {
+  const [gamePublicKey, _] = PublicKey.findProgramAddressSync(
+    [Buffer.from('game'), playerOne.publicKey.toBuffer(), Buffer.from(gameId)],
+    program.programId
+  );
+
+  // Airdrop to playerOne
+  await airdrop(programProvider, playerOne.publicKey);
+
+  await program.methods
+    .setupGame(playerTwo.publicKey, gameId)
+    .accounts({
+      game: gamePublicKey,
+      playerOne: playerOne.publicKey
+    })
+    .signers([playerOne])
+    .rpc();
+
+  return gamePublicKey;
+}
+
+async function play(
+  program: Program<TicTacToe>,
+  game: PublicKey,
+  player: Keypair,
+  tile: { row: number; column: number },
+  expectedTurn: number,
+  expectedGameState:
+    | { active: {} }
+    | { won: { winner: PublicKey } }
+    | { tie: {} },
+  expectedBoard: Array<Array<{ x: {} } | { o: {} } | null>>
+) {
+  await program.methods
+    .play(tile)
+    .accounts({
+      player: player.publicKey,
+      game
+    })
+    .signers([player])
+    .rpc();
+
+  const gameData = await program.account.game.fetch(game);
+
+  expect(gameData.turn).to.equal(expectedTurn);
+  expect(decodeState(gameData)).to.eql(expectedGameState);
+  expect(decodeBoard(gameData.boards)).to.eql(expectedBoard);
+}
+
+/**
+ * Expands the per-sign bitboards of a `Game` account,
+ * where tile `(row, column)` is bit `row * 3 + column`.
+ */
+function decodeBoard(
+  boards: number[]
+): Array<Array<{ x: {} } | { o: {} } | null>> {
+  const [x, o] = boards;
+  return [0, 1, 2].map(row =>
+    [0, 1, 2].map(column => {
+      const mask = 1 << (row * 3 + column);
+      if (x & mask) {
+        return { x: {} };
+      }
+      if (o & mask) {
+        return { o: {} };
+      }
+      return null;
+    })
+  );
+}
+
+function decodeState(gameData: {
+  state: number;
+  winner: PublicKey;
+}): { active: {} } | { won: { winner: PublicKey } } | { tie: {} } {
+  switch (gameData.state) {
+    case 1:
+      return { tie: {} };
+    case 2:
+      return { won: { winner: gameData.winner } };
+    default:
+      return { active: {} };
+  }
+}
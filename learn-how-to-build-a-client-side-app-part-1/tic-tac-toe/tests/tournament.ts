@@ -0,0 +1,317 @@
+import {
+  AnchorError,
+  Program,
+  AnchorProvider,
+  setProvider,
+  workspace
+} from '@coral-xyz/anchor';
+import { TicTacToe } from '../target/types/tic_tac_toe';
+import { Tournament } from '../target/types/tournament';
+import chai, { expect } from 'chai';
+import { Keypair, PublicKey } from '@solana/web3.js';
+
+describe('tournament', () => {
+  // Configure the client to use the local cluster.
+  setProvider(AnchorProvider.env());
+
+  const program = workspace.Tournament as Program<Tournament>;
+  const ticTacToe = workspace.TicTacToe as Program<TicTacToe>;
+  const programProvider = program.provider as AnchorProvider;
+
+  it('runs a bracket through to a champion', async () => {
+    const [tournamentPublicKey, players] = await setupTournament(
+      program,
+      programProvider,
+      'weekly-1',
+      4,
+      3
+    );
+
+    // seed 1 has a bye, seeds 2 and 3 meet in the second match
+    let tournamentData = await program.account.tournament.fetch(
+      tournamentPublicKey
+    );
+    expect(tournamentData.round).to.equal(1);
+    expect(tournamentData.bracket).to.eql([
+      players[0].publicKey,
+      null,
+      players[1].publicKey,
+      players[2].publicKey
+    ]);
+    expect(tournamentData.winners).to.eql([players[0].publicKey, null]);
+
+    const firstGame = await startMatch(
+      program,
+      tournamentPublicKey,
+      1,
+      1,
+      players[2]
+    );
+
+    try {
+      await advance(program, tournamentPublicKey, 1, firstGame);
+      chai.assert(false, "should've failed but didn't ");
+    } catch (_err) {
+      expect(_err).to.be.instanceOf(AnchorError);
+      const err: AnchorError = _err;
+      expect(err.error.errorCode.code).to.equal('MatchNotFinished');
+    }
+
+    await win(ticTacToe, firstGame, players[2], players[1]);
+    await advance(program, tournamentPublicKey, 1, firstGame);
+
+    tournamentData = await program.account.tournament.fetch(
+      tournamentPublicKey
+    );
+    expect(tournamentData.round).to.equal(2);
+    expect(tournamentData.bracket).to.eql([
+      players[0].publicKey,
+      players[2].publicKey
+    ]);
+
+    const final = await startMatch(
+      program,
+      tournamentPublicKey,
+      2,
+      0,
+      players[0]
+    );
+    await win(ticTacToe, final, players[0], players[2]);
+    await advance(program, tournamentPublicKey, 0, final);
+
+    tournamentData = await program.account.tournament.fetch(
+      tournamentPublicKey
+    );
+    expect(tournamentData.state).to.eql({
+      finished: { champion: players[0].publicKey }
+    });
+  });
+
+  it('pads a bracket that is not a power of two with byes', async () => {
+    const [tournamentPublicKey, players] = await setupTournament(
+      program,
+      programProvider,
+      'weekly-2',
+      3,
+      3
+    );
+
+    const tournamentData = await program.account.tournament.fetch(
+      tournamentPublicKey
+    );
+    expect(tournamentData.maxPlayers).to.equal(3);
+    expect(tournamentData.bracket).to.eql([
+      players[0].publicKey,
+      null,
+      players[1].publicKey,
+      players[2].publicKey
+    ]);
+    expect(tournamentData.games).to.eql([null, null]);
+    expect(tournamentData.winners).to.eql([players[0].publicKey, null]);
+  });
+
+  it('replays a tied match with a rematch', async () => {
+    const [tournamentPublicKey, players] = await setupTournament(
+      program,
+      programProvider,
+      'weekly-3',
+      2,
+      2
+    );
+
+    const game = await startMatch(
+      program,
+      tournamentPublicKey,
+      1,
+      0,
+      players[1]
+    );
+    await tie(ticTacToe, game, players[1], players[0]);
+
+    try {
+      await advance(program, tournamentPublicKey, 0, game);
+      chai.assert(false, "should've failed but didn't ");
+    } catch (_err) {
+      expect(_err).to.be.instanceOf(AnchorError);
+      const err: AnchorError = _err;
+      expect(err.error.errorCode.code).to.equal('MatchNotFinished');
+    }
+
+    // the rematch swaps who moves first
+    await ticTacToe.methods
+      .rematch()
+      .accounts({
+        game,
+        playerOne: players[0].publicKey,
+        playerTwo: players[1].publicKey
+      })
+      .signers(players)
+      .rpc();
+    await win(ticTacToe, game, players[0], players[1]);
+    await advance(program, tournamentPublicKey, 0, game);
+
+    const tournamentData = await program.account.tournament.fetch(
+      tournamentPublicKey
+    );
+    expect(tournamentData.state).to.eql({
+      finished: { champion: players[0].publicKey }
+    });
+  });
+});
+
+/**
+ * Creates a tournament for `maxPlayers`, registers `playerCount` new players
+ * and starts it.
+ */
+async function setupTournament(
+  program: Program<Tournament>,
+  programProvider: AnchorProvider,
+  tournamentId: string,
+  maxPlayers: number,
+  playerCount: number
+): Promise<[PublicKey, Keypair[]]> {
+  const organizer = Keypair.generate();
+  const players = Array.from({ length: playerCount }, () =>
+    Keypair.generate()
+  );
+  for (const keypair of [organizer, ...players]) {
+    const sg = await programProvider.connection.requestAirdrop(
+      keypair.publicKey,
+      1_000_000_000
+    );
+    await programProvider.connection.confirmTransaction(sg);
+  }
+
+  const [tournamentPublicKey, _] = PublicKey.findProgramAddressSync(
+    [
+      Buffer.from('tournament'),
+      organizer.publicKey.toBuffer(),
+      Buffer.from(tournamentId)
+    ],
+    program.programId
+  );
+
+  await program.methods
+    .createTournament(tournamentId, maxPlayers)
+    .accounts({
+      tournament: tournamentPublicKey,
+      organizer: organizer.publicKey
+    })
+    .signers([organizer])
+    .rpc();
+
+  for (const player of players) {
+    await program.methods
+      .register()
+      .accounts({ tournament: tournamentPublicKey, player: player.publicKey })
+      .signers([player])
+      .rpc();
+  }
+
+  await program.methods
+    .startTournament()
+    .accounts({
+      tournament: tournamentPublicKey,
+      organizer: organizer.publicKey
+    })
+    .signers([organizer])
+    .rpc();
+
+  return [tournamentPublicKey, players];
+}
+
+async function startMatch(
+  program: Program<Tournament>,
+  tournament: PublicKey,
+  round: number,
+  matchIndex: number,
+  player: Keypair
+): Promise<PublicKey> {
+  const prefix = tournament.toBuffer().subarray(0, 8).toString('hex');
+  const gameId = `${prefix}-${round}-${matchIndex}`;
+  const ticTacToe = workspace.TicTacToe as Program<TicTacToe>;
+  const [game] = PublicKey.findProgramAddressSync(
+    [Buffer.from('game'), player.publicKey.toBuffer(), Buffer.from(gameId)],
+    ticTacToe.programId
+  );
+
+  await program.methods
+    .startMatch(matchIndex)
+    .accounts({
+      tournament,
+      game,
+      player: player.publicKey,
+      ticTacToeProgram: ticTacToe.programId
+    })
+    .signers([player])
+    .rpc();
+
+  return game;
+}
+
+async function advance(
+  program: Program<Tournament>,
+  tournament: PublicKey,
+  matchIndex: number,
+  game: PublicKey
+) {
+  const ticTacToe = workspace.TicTacToe as Program<TicTacToe>;
+  await program.methods
+    .advance(matchIndex)
+    .accounts({ tournament, game, ticTacToeProgram: ticTacToe.programId })
+    .rpc();
+}
+
+/**
+ * Fills every tile without completing a line, `first` having started the game.
+ */
+async function tie(
+  program: Program<TicTacToe>,
+  game: PublicKey,
+  first: Keypair,
+  second: Keypair
+) {
+  const moves: Array<[Keypair, { row: number; column: number }]> = [
+    [first, { row: 0, column: 0 }],
+    [second, { row: 0, column: 1 }],
+    [first, { row: 0, column: 2 }],
+    [second, { row: 1, column: 1 }],
+    [first, { row: 1, column: 0 }],
+    [second, { row: 1, column: 2 }],
+    [first, { row: 2, column: 1 }],
+    [second, { row: 2, column: 0 }],
+    [first, { row: 2, column: 2 }]
+  ];
+  for (const [player, tile] of moves) {
+    await program.methods
+      .play(tile)
+      .accounts({ player: player.publicKey, game })
+      .signers([player])
+      .rpc();
+  }
+}
+
+/**
+ * Plays the top row for `winner`, who must have started the game.
+ */
+async function win(
+  program: Program<TicTacToe>,
+  game: PublicKey,
+  winner: Keypair,
+  loser: Keypair
+) {
+  const moves: Array<[Keypair, { row: number; column: number }]> = [
+    [winner, { row: 0, column: 0 }],
+    [loser, { row: 1, column: 0 }],
+    [winner, { row: 0, column: 1 }],
+    [loser, { row: 1, column: 1 }],
+    [winner, { row: 0, column: 2 }]
+  ];
+  for (const [player, tile] of moves) {
+    await program.methods
+      .play(tile)
+      .accounts({ player: player.publicKey, game })
+      .signers([player])
+      .rpc();
+  }
+}
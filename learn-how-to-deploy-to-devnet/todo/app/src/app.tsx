@@ -34,6 +34,8 @@ const PROGRAM_ID = new PublicKey(
 );
 const ENDPOINT =
   import.meta.env.VITE_SOLANA_CONNECTION_URL || 'http://localhost:8899';
+// The app keeps its tasks in the first list of the wallet.
+const LIST_INDEX = 0;
 const connection = new Connection(ENDPOINT, 'confirmed');
 const wallet = new PhantomWalletAdapter();
 const ProgramContext = createContext<Program<Todo> | null>(null);
@@ -59,6 +61,16 @@ export function App() {
   );
 }
 
+function findTasksPublicKey(user: PublicKey): PublicKey {
+  const listIndex = Buffer.alloc(4);
+  listIndex.writeUInt32LE(LIST_INDEX);
+  const [tasksPublicKey, _] = PublicKey.findProgramAddressSync(
+    [user.toBuffer(), listIndex],
+    PROGRAM_ID
+  );
+  return tasksPublicKey;
+}
+
 function LogIn({
   connectWallet
 }: {
@@ -81,10 +93,7 @@ function Landing() {
 
   async function loadTasksFromChain() {
     if (program && program.provider.publicKey) {
-      const [tasksPublicKey, _] = PublicKey.findProgramAddressSync(
-        [program.provider.publicKey.toBuffer()],
-        PROGRAM_ID
-      );
+      const tasksPublicKey = findTasksPublicKey(program.provider.publicKey);
       try {
         const tasks = await program.account.tasksAccount.fetch(tasksPublicKey);
         setTasks(tasks.tasks);
@@ -128,21 +137,53 @@ function Landing() {
 
   function addTask(name: string) {
     const id = Math.floor(Math.random() * 1_000_000);
-    const newTask = { id, name, completed: false };
+    const newTask = {
+      id,
+      name,
+      completed: false,
+      dueAt: null,
+      priority: 0,
+      tags: [],
+      notes: '',
+      parentId: null,
+      recurrence: null
+    };
     setTasks([...tasks, newTask]);
   }
 
   async function saveTasksToChain() {
     if (program && program.provider.publicKey) {
-      const [tasksPublicKey, _] = PublicKey.findProgramAddressSync(
-        [program.provider.publicKey.toBuffer()],
-        PROGRAM_ID
-      );
+      const user = program.provider.publicKey;
+      const tasksPublicKey = findTasksPublicKey(user);
+
+      // The list is created along with the first save.
+      const preInstructions = [];
+      if (!(await connection.getAccountInfo(tasksPublicKey))) {
+        const [directoryPublicKey, _] = PublicKey.findProgramAddressSync(
+          [Buffer.from('list_directory'), user.toBuffer()],
+          PROGRAM_ID
+        );
+        preInstructions.push(
+          await program.methods
+            .createList('ToDos')
+            .accounts({
+              directory: directoryPublicKey,
+              tasks: tasksPublicKey,
+              owner: user
+            })
+            .instruction()
+        );
+      }
+
       await program.methods
-        .saveTasks(tasks)
+        .saveTasks(LIST_INDEX, tasks)
         .accounts({
-          tasks: tasksPublicKey
+          tasks: tasksPublicKey,
+          taskList: null,
+          owner: user,
+          user
         })
+        .preInstructions(preInstructions)
         .rpc();
     }
   }
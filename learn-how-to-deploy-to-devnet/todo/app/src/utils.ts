@@ -1,10 +1,16 @@
-import { Wallet } from '@coral-xyz/anchor';
+import { BN, Wallet } from '@coral-xyz/anchor';
 import { useEffect, useRef } from 'react';
 
 export type Task = {
   id: number;
   name: string;
   completed: boolean;
+  dueAt: BN | null;
+  priority: number;
+  tags: string[];
+  notes: string;
+  parentId: number | null;
+  recurrence: { interval: BN; nextDueAt: BN } | null;
 };
 
 export type TodoT = {
@@ -1,96 +1,1202 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
 
 declare_id!("9a43FDYE3S98dfN1rPAeavJT6MzBUEuF3bdX94zihQG2");
 
-const TASK_SIZE: usize = 4 + (4 + 32) + 1;
+/// The maximum number of tasks in the tasks account of a list. Lists that need more
+/// tasks can store them in pages.
+const MAX_TASKS: usize = 32;
+/// The maximum length of a task name, in bytes.
+const MAXIMUM_NAME_LENGTH: usize = 32;
+/// The maximum number of tags on a task.
+const MAXIMUM_TAGS: usize = 5;
+/// The maximum length of a tag, in bytes.
+const MAXIMUM_TAG_LENGTH: usize = 16;
+/// The maximum length of the notes of a task, in bytes.
+const MAXIMUM_NOTES_LENGTH: usize = 256;
+/// The highest priority a task can have. `0` means no priority.
+const MAXIMUM_PRIORITY: u8 = 3;
 
 #[program]
 pub mod todo {
     use super::*;
 
-    pub fn save_tasks(ctx: Context<SaveTasks>, replacing_tasks: Vec<Task>) -> Result<()> {
+    pub fn create_list(ctx: Context<CreateList>, name: String) -> Result<()> {
+        let directory = &mut ctx.accounts.directory;
+
+        validate_list_name(&name)?;
+
+        if directory.lists.len() >= ListDirectory::MAXIMUM_LISTS {
+            return Err(ErrorCode::TooManyLists.into());
+        }
+
+        let index = directory.next_index;
+        directory.lists.push(ListEntry { index, name });
+        directory.next_index += 1;
+
+        Ok(())
+    }
+
+    /// Closes a list, and its task list if it is shared, and refunds their rent to the
+    /// owner. Closing a list that still has incomplete tasks must be confirmed with
+    /// `confirm_incomplete`.
+    pub fn close_list(
+        ctx: Context<CloseList>,
+        list_index: u32,
+        confirm_incomplete: bool,
+    ) -> Result<()> {
+        remove_list(
+            &mut ctx.accounts.directory,
+            &ctx.accounts.tasks,
+            list_index,
+            confirm_incomplete,
+        )
+    }
+
+    /// Closes the tasks account of a list that isn't shared, and refunds its rent to
+    /// the user who owns it. Like `close_list`, closing a list that still has
+    /// incomplete tasks must be confirmed with `confirm_incomplete`.
+    pub fn close_tasks(
+        ctx: Context<CloseTasks>,
+        list_index: u32,
+        confirm_incomplete: bool,
+    ) -> Result<()> {
+        remove_list(
+            &mut ctx.accounts.directory,
+            &ctx.accounts.tasks,
+            list_index,
+            confirm_incomplete,
+        )
+    }
+
+    pub fn save_tasks(
+        ctx: Context<ModifyTasks>,
+        _list_index: u32,
+        replacing_tasks: Vec<Task>,
+    ) -> Result<()> {
         let tasks = &mut ctx.accounts.tasks;
 
-        // Check that the task name is not too long.
+        if replacing_tasks.len() > MAX_TASKS {
+            return Err(ErrorCode::TooManyTasks.into());
+        }
+
         for task in replacing_tasks.iter() {
-            if task.name.len() > 32 {
-                return Err(ErrorCode::TaskNameTooLong.into());
-            }
+            validate_task(task)?;
+        }
+
+        // Check that the task id is unique. Sorting the ids puts duplicates next to each other.
+        let mut ids: Vec<u32> = replacing_tasks.iter().map(|task| task.id).collect();
+        ids.sort_unstable();
+        if ids.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(ErrorCode::TaskIdNotUnique.into());
         }
 
-        // Check that the task name is not too short.
+        validate_parents(&replacing_tasks)?;
+
+        // Record the tasks that are new, or newly completed.
+        let user = ctx.accounts.user.key();
+        let mut previous_tasks: Vec<(u32, bool)> = tasks
+            .tasks
+            .iter()
+            .map(|task| (task.id, task.completed))
+            .collect();
+        previous_tasks.sort_unstable();
         for task in replacing_tasks.iter() {
-            if task.name.len() < 1 {
-                return Err(ErrorCode::TaskNameTooShort.into());
+            match previous_tasks.binary_search_by_key(&task.id, |&(id, _)| id) {
+                Ok(index) => {
+                    if task.completed && !previous_tasks[index].1 {
+                        record_completed(tasks, task.id, user);
+                    }
+                }
+                Err(_) => record_created(tasks, task, user),
             }
         }
 
+        emit!(TasksReplaced {
+            list: tasks.key(),
+            user,
+            task_count: replacing_tasks.len() as u32,
+        });
+
+        tasks.tasks = replacing_tasks;
+
+        reallocate(
+            tasks,
+            &ctx.accounts.user,
+            &ctx.accounts.owner,
+            &ctx.accounts.system_program,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn add_task(ctx: Context<ModifyTasks>, _list_index: u32, task: Task) -> Result<()> {
+        let tasks = &mut ctx.accounts.tasks;
+
+        validate_task(&task)?;
+
+        if tasks.tasks.len() >= MAX_TASKS {
+            return Err(ErrorCode::TooManyTasks.into());
+        }
+
         // Check that the task id is unique.
-        for (i, task) in replacing_tasks.iter().enumerate() {
-            for (j, other_task) in replacing_tasks.iter().enumerate() {
-                if i != j && task.id == other_task.id {
-                    return Err(ErrorCode::TaskIdNotUnique.into());
+        if tasks
+            .tasks
+            .iter()
+            .any(|other_task| other_task.id == task.id)
+        {
+            return Err(ErrorCode::TaskIdNotUnique.into());
+        }
+
+        // No task can have the new task as its parent yet, so it can't form a cycle.
+        if let Some(parent_id) = task.parent_id {
+            tasks
+                .position(parent_id)
+                .map_err(|_| ErrorCode::ParentNotFound)?;
+        }
+
+        record_created(tasks, &task, ctx.accounts.user.key());
+        tasks.tasks.push(task);
+
+        reallocate(
+            tasks,
+            &ctx.accounts.user,
+            &ctx.accounts.owner,
+            &ctx.accounts.system_program,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn update_task(
+        ctx: Context<ModifyTasks>,
+        _list_index: u32,
+        id: u32,
+        name: String,
+    ) -> Result<()> {
+        let tasks = &mut ctx.accounts.tasks;
+
+        validate_task_name(&name)?;
+
+        let index = tasks.position(id)?;
+        tasks.tasks[index].name = name;
+
+        reallocate(
+            tasks,
+            &ctx.accounts.user,
+            &ctx.accounts.owner,
+            &ctx.accounts.system_program,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn update_task_details(
+        ctx: Context<ModifyTasks>,
+        _list_index: u32,
+        id: u32,
+        due_at: Option<i64>,
+        priority: u8,
+        tags: Vec<String>,
+        notes: String,
+    ) -> Result<()> {
+        let tasks = &mut ctx.accounts.tasks;
+
+        validate_task_details(priority, &tags, &notes)?;
+
+        let index = tasks.position(id)?;
+        let task = &mut tasks.tasks[index];
+        task.due_at = due_at;
+        task.priority = priority;
+        task.tags = tags;
+        task.notes = notes;
+        // Moving the due date of a recurring task moves its current occurrence.
+        if let Some(recurrence) = task.recurrence.as_mut() {
+            recurrence.next_due_at = due_at.ok_or(ErrorCode::InvalidRecurrence)?;
+        }
+
+        reallocate(
+            tasks,
+            &ctx.accounts.user,
+            &ctx.accounts.owner,
+            &ctx.accounts.system_program,
+        )?;
+
+        Ok(())
+    }
+
+    /// Makes a task recur every `recurrence.interval` seconds, or stop recurring.
+    pub fn set_task_recurrence(
+        ctx: Context<ModifyTasks>,
+        _list_index: u32,
+        id: u32,
+        recurrence: Option<Recurrence>,
+    ) -> Result<()> {
+        let tasks = &mut ctx.accounts.tasks;
+
+        validate_recurrence(recurrence.as_ref())?;
+
+        let index = tasks.position(id)?;
+        let task = &mut tasks.tasks[index];
+        task.recurrence = recurrence;
+        if let Some(recurrence) = recurrence {
+            task.due_at = Some(recurrence.next_due_at);
+        }
+
+        reallocate(
+            tasks,
+            &ctx.accounts.user,
+            &ctx.accounts.owner,
+            &ctx.accounts.system_program,
+        )?;
+
+        Ok(())
+    }
+
+    /// Starts the next occurrence of every completed recurring task that is past its
+    /// due time, skipping the occurrences that were missed. Incomplete tasks stay
+    /// overdue until they are completed.
+    pub fn roll_recurring(ctx: Context<ModifyTasks>, _list_index: u32) -> Result<()> {
+        let tasks = &mut ctx.accounts.tasks;
+        let now = Clock::get()?.unix_timestamp;
+
+        for task in tasks.tasks.iter_mut() {
+            if !task.completed {
+                continue;
+            }
+            let Some(recurrence) = task.recurrence.as_mut() else {
+                continue;
+            };
+            if now < recurrence.next_due_at {
+                continue;
+            }
+
+            let occurrences = now
+                .checked_sub(recurrence.next_due_at)
+                .ok_or(ErrorCode::InvalidRecurrence)?
+                / recurrence.interval
+                + 1;
+            recurrence.next_due_at = occurrences
+                .checked_mul(recurrence.interval)
+                .and_then(|elapsed| recurrence.next_due_at.checked_add(elapsed))
+                .ok_or(ErrorCode::InvalidRecurrence)?;
+            task.due_at = Some(recurrence.next_due_at);
+            task.completed = false;
+        }
+
+        reallocate(
+            tasks,
+            &ctx.accounts.user,
+            &ctx.accounts.owner,
+            &ctx.accounts.system_program,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn set_task_parent(
+        ctx: Context<ModifyTasks>,
+        _list_index: u32,
+        id: u32,
+        parent_id: Option<u32>,
+    ) -> Result<()> {
+        let tasks = &mut ctx.accounts.tasks;
+
+        let index = tasks.position(id)?;
+        tasks.tasks[index].parent_id = parent_id;
+
+        validate_parents(&tasks.tasks)?;
+
+        reallocate(
+            tasks,
+            &ctx.accounts.user,
+            &ctx.accounts.owner,
+            &ctx.accounts.system_program,
+        )?;
+
+        Ok(())
+    }
+
+    /// Toggles whether a task is completed. Completing a task with `cascade`
+    /// also completes all the tasks below it.
+    pub fn toggle_task(
+        ctx: Context<ModifyTasks>,
+        _list_index: u32,
+        id: u32,
+        cascade: bool,
+    ) -> Result<()> {
+        let tasks = &mut ctx.accounts.tasks;
+        let user = ctx.accounts.user.key();
+
+        let index = tasks.position(id)?;
+        tasks.tasks[index].completed = !tasks.tasks[index].completed;
+
+        if tasks.tasks[index].completed {
+            record_completed(tasks, id, user);
+
+            if cascade {
+                for descendant_id in descendants(&tasks.tasks, id) {
+                    let index = tasks.position(descendant_id)?;
+                    if !tasks.tasks[index].completed {
+                        tasks.tasks[index].completed = true;
+                        record_completed(tasks, descendant_id, user);
+                    }
                 }
             }
         }
 
-        // If length of tasks is not equal to the length of replacing_tasks, then
-        // reallocate the tasks account.
-        if tasks.tasks.len() < replacing_tasks.len() {
-            let new_space = 8 + TASK_SIZE * replacing_tasks.len();
+        Ok(())
+    }
 
-            let new_minimum_balance = Rent::get()?.minimum_balance(new_space);
+    /// Deletes a task. Deleting a task with other tasks below it requires `cascade`,
+    /// which deletes them too.
+    pub fn delete_task(
+        ctx: Context<ModifyTasks>,
+        _list_index: u32,
+        id: u32,
+        cascade: bool,
+    ) -> Result<()> {
+        let tasks = &mut ctx.accounts.tasks;
 
-            let tasks_account_info = tasks.to_account_info();
+        tasks.position(id)?;
+        let descendant_ids = descendants(&tasks.tasks, id);
+        if !descendant_ids.is_empty() && !cascade {
+            return Err(ErrorCode::TaskHasChildren.into());
+        }
 
-            let lamports_diff = new_minimum_balance.saturating_sub(tasks_account_info.lamports());
-            **ctx
-                .accounts
-                .user
-                .to_account_info()
-                .try_borrow_mut_lamports()? -= lamports_diff;
-            **tasks_account_info.try_borrow_mut_lamports()? += lamports_diff;
+        tasks
+            .tasks
+            .retain(|task| task.id != id && !descendant_ids.contains(&task.id));
 
-            // Allocate the new space for the tasks account.
-            tasks_account_info.realloc(new_space, false)?;
+        for id in std::iter::once(id).chain(descendant_ids) {
+            emit!(TaskDeleted {
+                list: tasks.key(),
+                user: ctx.accounts.user.key(),
+                id,
+            });
         }
 
-        tasks.tasks = replacing_tasks;
+        reallocate(
+            tasks,
+            &ctx.accounts.user,
+            &ctx.accounts.owner,
+            &ctx.accounts.system_program,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn reorder_tasks(ctx: Context<ModifyTasks>, _list_index: u32, ids: Vec<u32>) -> Result<()> {
+        let tasks = &mut ctx.accounts.tasks;
+
+        // Check that every task is listed exactly once.
+        if ids.len() != tasks.tasks.len() {
+            return Err(ErrorCode::InvalidTaskOrder.into());
+        }
+        let mut reordered_tasks: Vec<Task> = Vec::with_capacity(ids.len());
+        for id in ids {
+            if reordered_tasks.iter().any(|task| task.id == id) {
+                return Err(ErrorCode::TaskIdNotUnique.into());
+            }
+            let index = tasks.position(id)?;
+            reordered_tasks.push(tasks.tasks[index].clone());
+        }
+
+        tasks.tasks = reordered_tasks;
+
+        Ok(())
+    }
+
+    /// Adds a page to the list, for lists with more tasks than fit in the tasks account.
+    pub fn append_page(ctx: Context<AppendPage>, _list_index: u32) -> Result<()> {
+        let tasks = &mut ctx.accounts.tasks;
+
+        tasks.page_count += 1;
+
+        Ok(())
+    }
+
+    /// Closes the last page of the list, which must be empty, and refunds its rent
+    /// to the owner.
+    pub fn close_page(ctx: Context<ClosePage>, _list_index: u32) -> Result<()> {
+        let tasks = &mut ctx.accounts.tasks;
+
+        if !ctx.accounts.page.tasks.is_empty() {
+            return Err(ErrorCode::PageNotEmpty.into());
+        }
+
+        tasks.page_count -= 1;
+
+        Ok(())
+    }
+
+    pub fn add_page_task(
+        ctx: Context<ModifyPage>,
+        _list_index: u32,
+        _page_index: u32,
+        task: Task,
+    ) -> Result<()> {
+        let page = &mut ctx.accounts.page;
+
+        validate_task(&task)?;
+
+        if page.tasks.len() >= TaskPage::MAXIMUM_TASKS {
+            return Err(ErrorCode::PageFull.into());
+        }
+
+        // Check that the task id is unique within the page.
+        if page.tasks.iter().any(|other_task| other_task.id == task.id) {
+            return Err(ErrorCode::TaskIdNotUnique.into());
+        }
+
+        // The parent of a task in a page must be in the same page.
+        if let Some(parent_id) = task.parent_id {
+            if !page
+                .tasks
+                .iter()
+                .any(|other_task| other_task.id == parent_id)
+            {
+                return Err(ErrorCode::ParentNotFound.into());
+            }
+        }
+
+        record_created(&mut ctx.accounts.tasks, &task, ctx.accounts.user.key());
+        page.tasks.push(task);
+
+        Ok(())
+    }
+
+    pub fn update_page_task(
+        ctx: Context<ModifyPage>,
+        _list_index: u32,
+        _page_index: u32,
+        index: u32,
+        task: Task,
+    ) -> Result<()> {
+        let page = &mut ctx.accounts.page;
+
+        validate_task(&task)?;
+
+        let index = page.index(index)?;
+        if page
+            .tasks
+            .iter()
+            .enumerate()
+            .any(|(other_index, other_task)| other_index != index && other_task.id == task.id)
+        {
+            return Err(ErrorCode::TaskIdNotUnique.into());
+        }
+
+        if task.completed && !page.tasks[index].completed {
+            record_completed(&mut ctx.accounts.tasks, task.id, ctx.accounts.user.key());
+        }
+        page.tasks[index] = task;
+
+        validate_parents(&page.tasks)?;
+
+        Ok(())
+    }
+
+    pub fn delete_page_task(
+        ctx: Context<ModifyPage>,
+        _list_index: u32,
+        _page_index: u32,
+        index: u32,
+    ) -> Result<()> {
+        let page = &mut ctx.accounts.page;
+
+        let index = page.index(index)?;
+        let id = page.tasks[index].id;
+        if page.tasks.iter().any(|task| task.parent_id == Some(id)) {
+            return Err(ErrorCode::TaskHasChildren.into());
+        }
+        let task = page.tasks.remove(index);
+
+        emit!(TaskDeleted {
+            list: ctx.accounts.tasks.key(),
+            user: ctx.accounts.user.key(),
+            id: task.id,
+        });
+
+        Ok(())
+    }
+
+    pub fn create_task_list(ctx: Context<CreateTaskList>, list_index: u32) -> Result<()> {
+        let task_list = &mut ctx.accounts.task_list;
+
+        task_list.owner = ctx.accounts.owner.key();
+        task_list.list_index = list_index;
+
+        Ok(())
+    }
+
+    pub fn invite_member(ctx: Context<ManageMembers>, member: Pubkey, role: Role) -> Result<()> {
+        let task_list = &mut ctx.accounts.task_list;
+
+        if !has_role(
+            task_list.owner,
+            Some(task_list),
+            ctx.accounts.user.key(),
+            Role::Admin,
+        ) {
+            return Err(ErrorCode::Unauthorized.into());
+        }
+
+        // Check that the member is not on the list yet.
+        if member == task_list.owner || task_list.role_of(member).is_some() {
+            return Err(ErrorCode::AlreadyAMember.into());
+        }
+
+        if task_list.members.len() >= TaskList::MAXIMUM_MEMBERS {
+            return Err(ErrorCode::TooManyMembers.into());
+        }
+
+        task_list.members.push(Member { key: member, role });
+
+        Ok(())
+    }
+
+    pub fn remove_member(ctx: Context<ManageMembers>, member: Pubkey) -> Result<()> {
+        let task_list = &mut ctx.accounts.task_list;
+        let user = ctx.accounts.user.key();
+
+        // Members can always leave a list.
+        if user != member && !has_role(task_list.owner, Some(task_list), user, Role::Admin) {
+            return Err(ErrorCode::Unauthorized.into());
+        }
+
+        let index = task_list
+            .members
+            .iter()
+            .position(|other_member| other_member.key == member)
+            .ok_or(ErrorCode::NotAMember)?;
+        task_list.members.remove(index);
 
         Ok(())
     }
 }
 
+/// Checks that the parent of every task is one of `tasks`,
+/// and that following the parents never leads back to the same task.
+fn validate_parents(tasks: &[Task]) -> Result<()> {
+    let mut indices: Vec<(u32, usize)> = tasks
+        .iter()
+        .enumerate()
+        .map(|(index, task)| (task.id, index))
+        .collect();
+    indices.sort_unstable();
+    let parent_index = |task: &Task| -> Result<Option<usize>> {
+        match task.parent_id {
+            Some(parent_id) => indices
+                .binary_search_by_key(&parent_id, |&(id, _)| id)
+                .map(|position| Some(indices[position].1))
+                .map_err(|_| ErrorCode::ParentNotFound.into()),
+            None => Ok(None),
+        }
+    };
+
+    // Walk up the parents from every task, marking each task with the walk that
+    // reached it first. Reaching a task marked by the same walk means a cycle.
+    let mut walks: Vec<Option<usize>> = vec![None; tasks.len()];
+    for start in 0..tasks.len() {
+        let mut index = start;
+        loop {
+            match walks[index] {
+                Some(walk) if walk == start => return Err(ErrorCode::TaskCycle.into()),
+                // The rest of the way up was checked by an earlier walk.
+                Some(_) => break,
+                None => walks[index] = Some(start),
+            }
+            match parent_index(&tasks[index])? {
+                Some(parent) => index = parent,
+                None => break,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The ids of all the tasks below the task `id`.
+fn descendants(tasks: &[Task], id: u32) -> Vec<u32> {
+    let mut descendant_ids = Vec::new();
+    let mut parent_ids = vec![id];
+    while let Some(parent_id) = parent_ids.pop() {
+        for task in tasks
+            .iter()
+            .filter(|task| task.parent_id == Some(parent_id))
+        {
+            descendant_ids.push(task.id);
+            parent_ids.push(task.id);
+        }
+    }
+    descendant_ids
+}
+
+/// Counts a new task of the list, and emits `TaskCreated`.
+fn record_created(tasks: &mut Account<TasksAccount>, task: &Task, user: Pubkey) {
+    tasks.created_count += 1;
+    emit!(TaskCreated {
+        list: tasks.key(),
+        user,
+        id: task.id,
+    });
+
+    if task.completed {
+        record_completed(tasks, task.id, user);
+    }
+}
+
+/// Counts a completed task of the list, and emits `TaskCompleted`.
+fn record_completed(tasks: &mut Account<TasksAccount>, id: u32, user: Pubkey) {
+    tasks.completed_count += 1;
+    emit!(TaskCompleted {
+        list: tasks.key(),
+        user,
+        id,
+    });
+}
+
+/// Removes a list from the directory before its tasks account is closed.
+fn remove_list(
+    directory: &mut ListDirectory,
+    tasks: &TasksAccount,
+    list_index: u32,
+    confirm_incomplete: bool,
+) -> Result<()> {
+    if tasks.page_count > 0 {
+        return Err(ErrorCode::PagesRemain.into());
+    }
+
+    if !confirm_incomplete && tasks.tasks.iter().any(|task| !task.completed) {
+        return Err(ErrorCode::IncompleteTasksRemain.into());
+    }
+
+    let position = directory
+        .lists
+        .iter()
+        .position(|list| list.index == list_index)
+        .ok_or(ErrorCode::ListNotFound)?;
+    directory.lists.remove(position);
+
+    Ok(())
+}
+
+/// Whether `user` has at least `role` on the tasks of `owner`.
+/// The owner is an admin of their tasks, even when they are not shared.
+fn has_role(owner: Pubkey, task_list: Option<&TaskList>, user: Pubkey, role: Role) -> bool {
+    let user_role = if user == owner {
+        Some(Role::Admin)
+    } else {
+        task_list.and_then(|task_list| task_list.role_of(user))
+    };
+
+    user_role.map_or(false, |user_role| user_role >= role)
+}
+
+fn validate_list_name(name: &str) -> Result<()> {
+    if name.len() > ListDirectory::MAXIMUM_NAME_LENGTH {
+        return Err(ErrorCode::ListNameTooLong.into());
+    }
+
+    if name.is_empty() {
+        return Err(ErrorCode::ListNameTooShort.into());
+    }
+
+    Ok(())
+}
+
+fn validate_task(task: &Task) -> Result<()> {
+    validate_task_name(&task.name)?;
+    validate_task_details(task.priority, &task.tags, &task.notes)?;
+    validate_recurrence(task.recurrence.as_ref())?;
+
+    // A recurring task is due when its current occurrence is.
+    if let Some(recurrence) = task.recurrence {
+        if task.due_at != Some(recurrence.next_due_at) {
+            return Err(ErrorCode::InvalidRecurrence.into());
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_recurrence(recurrence: Option<&Recurrence>) -> Result<()> {
+    if recurrence.map_or(false, |recurrence| recurrence.interval <= 0) {
+        return Err(ErrorCode::InvalidRecurrence.into());
+    }
+
+    Ok(())
+}
+
+fn validate_task_name(name: &str) -> Result<()> {
+    // Check that the task name is not too long. The length is in bytes,
+    // as that is what the name takes up in the account.
+    if name.len() > MAXIMUM_NAME_LENGTH {
+        return Err(ErrorCode::TaskNameTooLong.into());
+    }
+
+    // Check that the task name is not too short.
+    if name.is_empty() {
+        return Err(ErrorCode::TaskNameTooShort.into());
+    }
+
+    Ok(())
+}
+
+fn validate_task_details(priority: u8, tags: &[String], notes: &str) -> Result<()> {
+    if priority > MAXIMUM_PRIORITY {
+        return Err(ErrorCode::InvalidPriority.into());
+    }
+
+    if tags.len() > MAXIMUM_TAGS {
+        return Err(ErrorCode::TooManyTags.into());
+    }
+    for tag in tags {
+        if tag.len() > MAXIMUM_TAG_LENGTH {
+            return Err(ErrorCode::TagTooLong.into());
+        }
+        if tag.is_empty() {
+            return Err(ErrorCode::TagTooShort.into());
+        }
+    }
+
+    if notes.len() > MAXIMUM_NOTES_LENGTH {
+        return Err(ErrorCode::NotesTooLong.into());
+    }
+
+    Ok(())
+}
+
+/// Resizes the tasks account to fit its current tasks. The user pays the extra rent
+/// when the account grows, and the owner is refunded the excess rent when it
+/// shrinks, so that members can't drain rent the owner paid.
+fn reallocate<'info>(
+    tasks: &Account<'info, TasksAccount>,
+    user: &Signer<'info>,
+    owner: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let tasks_account_info = tasks.to_account_info();
+    let new_space = TasksAccount::space(&tasks.tasks);
+
+    if tasks_account_info.data_len() == new_space {
+        return Ok(());
+    }
+
+    let new_minimum_balance = Rent::get()?.minimum_balance(new_space);
+    let lamports = tasks_account_info.lamports();
+    if new_minimum_balance > lamports {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: user.to_account_info(),
+                    to: tasks_account_info.clone(),
+                },
+            ),
+            new_minimum_balance - lamports,
+        )?;
+    } else {
+        let refund = lamports - new_minimum_balance;
+        **tasks_account_info.try_borrow_mut_lamports()? -= refund;
+        **owner.try_borrow_mut_lamports()? += refund;
+    }
+
+    // Allocate the new space for the tasks account.
+    tasks_account_info.realloc(new_space, false)?;
+
+    Ok(())
+}
+
 #[derive(Accounts)]
-#[instruction(replacing_tasks: Vec<Task>)]
-pub struct SaveTasks<'info> {
-    #[account(init_if_needed, space = 8 + replacing_tasks.len() * TASK_SIZE, payer = user, seeds = [user.key().as_ref()], bump)]
+pub struct CreateList<'info> {
+    #[account(init_if_needed, space = 8 + ListDirectory::SIZE, payer = owner, seeds = [b"list_directory", owner.key().as_ref()], bump)]
+    pub directory: Account<'info, ListDirectory>,
+    #[account(init, space = TasksAccount::space(&[]), payer = owner, seeds = [owner.key().as_ref(), directory.next_index.to_le_bytes().as_ref()], bump)]
     pub tasks: Account<'info, TasksAccount>,
     #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(list_index: u32)]
+pub struct CloseList<'info> {
+    #[account(mut, seeds = [b"list_directory", owner.key().as_ref()], bump)]
+    pub directory: Account<'info, ListDirectory>,
+    #[account(mut, close = owner, seeds = [owner.key().as_ref(), list_index.to_le_bytes().as_ref()], bump)]
+    pub tasks: Account<'info, TasksAccount>,
+    #[account(mut, close = owner, seeds = [b"task_list", owner.key().as_ref(), list_index.to_le_bytes().as_ref()], bump, has_one = owner)]
+    pub task_list: Option<Account<'info, TaskList>>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(list_index: u32)]
+pub struct CloseTasks<'info> {
+    #[account(mut, seeds = [b"list_directory", user.key().as_ref()], bump)]
+    pub directory: Account<'info, ListDirectory>,
+    #[account(mut, close = user, seeds = [user.key().as_ref(), list_index.to_le_bytes().as_ref()], bump)]
+    pub tasks: Account<'info, TasksAccount>,
+    /// CHECK: must not exist, so that its rent isn't left behind
+    #[account(seeds = [b"task_list", user.key().as_ref(), list_index.to_le_bytes().as_ref()], bump, constraint = task_list.data_is_empty() @ ErrorCode::ListIsShared)]
+    pub task_list: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(list_index: u32)]
+pub struct ModifyTasks<'info> {
+    #[account(mut, seeds = [owner.key().as_ref(), list_index.to_le_bytes().as_ref()], bump)]
+    pub tasks: Account<'info, TasksAccount>,
+    #[account(seeds = [b"task_list", owner.key().as_ref(), list_index.to_le_bytes().as_ref()], bump, has_one = owner)]
+    pub task_list: Option<Account<'info, TaskList>>,
+    /// CHECK: only used as a seed, and refunded when the tasks shrink
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+    #[account(mut, constraint = has_role(owner.key(), task_list.as_deref(), user.key(), Role::Editor) @ ErrorCode::Unauthorized)]
     pub user: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+#[instruction(list_index: u32)]
+pub struct AppendPage<'info> {
+    #[account(mut, seeds = [owner.key().as_ref(), list_index.to_le_bytes().as_ref()], bump)]
+    pub tasks: Account<'info, TasksAccount>,
+    #[account(init, space = 8 + TaskPage::SIZE, payer = user, seeds = [b"page", tasks.key().as_ref(), tasks.page_count.to_le_bytes().as_ref()], bump)]
+    pub page: Account<'info, TaskPage>,
+    #[account(seeds = [b"task_list", owner.key().as_ref(), list_index.to_le_bytes().as_ref()], bump, has_one = owner)]
+    pub task_list: Option<Account<'info, TaskList>>,
+    /// CHECK: only used as a seed
+    pub owner: UncheckedAccount<'info>,
+    #[account(mut, constraint = has_role(owner.key(), task_list.as_deref(), user.key(), Role::Editor) @ ErrorCode::Unauthorized)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(list_index: u32)]
+pub struct ClosePage<'info> {
+    #[account(mut, seeds = [owner.key().as_ref(), list_index.to_le_bytes().as_ref()], bump, constraint = tasks.page_count > 0 @ ErrorCode::NoPages)]
+    pub tasks: Account<'info, TasksAccount>,
+    #[account(mut, close = owner, seeds = [b"page", tasks.key().as_ref(), (tasks.page_count - 1).to_le_bytes().as_ref()], bump)]
+    pub page: Account<'info, TaskPage>,
+    #[account(seeds = [b"task_list", owner.key().as_ref(), list_index.to_le_bytes().as_ref()], bump, has_one = owner)]
+    pub task_list: Option<Account<'info, TaskList>>,
+    /// CHECK: only used as a seed, and refunded the rent of the page
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+    #[account(constraint = has_role(owner.key(), task_list.as_deref(), user.key(), Role::Editor) @ ErrorCode::Unauthorized)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(list_index: u32, page_index: u32)]
+pub struct ModifyPage<'info> {
+    #[account(mut, seeds = [owner.key().as_ref(), list_index.to_le_bytes().as_ref()], bump)]
+    pub tasks: Account<'info, TasksAccount>,
+    #[account(mut, seeds = [b"page", tasks.key().as_ref(), page_index.to_le_bytes().as_ref()], bump)]
+    pub page: Account<'info, TaskPage>,
+    #[account(seeds = [b"task_list", owner.key().as_ref(), list_index.to_le_bytes().as_ref()], bump, has_one = owner)]
+    pub task_list: Option<Account<'info, TaskList>>,
+    /// CHECK: only used as a seed
+    pub owner: UncheckedAccount<'info>,
+    #[account(constraint = has_role(owner.key(), task_list.as_deref(), user.key(), Role::Editor) @ ErrorCode::Unauthorized)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(list_index: u32)]
+pub struct CreateTaskList<'info> {
+    #[account(init, space = 8 + TaskList::SIZE, payer = owner, seeds = [b"task_list", owner.key().as_ref(), list_index.to_le_bytes().as_ref()], bump)]
+    pub task_list: Account<'info, TaskList>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageMembers<'info> {
+    #[account(mut, seeds = [b"task_list", task_list.owner.as_ref(), task_list.list_index.to_le_bytes().as_ref()], bump)]
+    pub task_list: Account<'info, TaskList>,
+    pub user: Signer<'info>,
+}
+
+/// The lists of a user, so that clients can find them.
+#[account]
+pub struct ListDirectory {
+    next_index: u32,       // 4
+    lists: Vec<ListEntry>, // 4 + (MAXIMUM_LISTS * (4 + (4 + MAXIMUM_NAME_LENGTH)))
+}
+
+impl ListDirectory {
+    pub const MAXIMUM_LISTS: usize = 16;
+    pub const MAXIMUM_NAME_LENGTH: usize = 32;
+    pub const SIZE: usize = 4 + (4 + Self::MAXIMUM_LISTS * (4 + (4 + Self::MAXIMUM_NAME_LENGTH)));
+}
+
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ListEntry {
+    /// The index in the seeds of the tasks account of the list.
+    pub index: u32,
+    /// Max 32 bytes, min 1 byte.
+    pub name: String,
+}
+
 #[account]
 #[derive(Debug)]
 pub struct TasksAccount {
     tasks: Vec<Task>,
+    /// The number of `TaskPage` accounts of the list, seeded by
+    /// `[b"page", tasks.key(), page_index]`.
+    page_count: u32,
+    /// The number of tasks ever created in the list.
+    created_count: u64,
+    /// The number of times a task of the list was completed.
+    completed_count: u64,
+}
+
+impl TasksAccount {
+    /// The account space needed to store `tasks`, including the discriminator.
+    fn space(tasks: &[Task]) -> usize {
+        8 + tasks.serialized_size() + 4 + 8 + 8
+    }
+
+    fn position(&self, id: u32) -> Result<usize> {
+        self.tasks
+            .iter()
+            .position(|task| task.id == id)
+            .ok_or_else(|| ErrorCode::TaskNotFound.into())
+    }
+}
+
+/// A fixed-size page of tasks, so that a list can hold more tasks than fit in one account.
+#[account]
+pub struct TaskPage {
+    tasks: Vec<Task>, // 4 + (MAXIMUM_TASKS * Task::MAXIMUM_SIZE)
+}
+
+impl TaskPage {
+    pub const MAXIMUM_TASKS: usize = 16;
+    pub const SIZE: usize = 4 + Self::MAXIMUM_TASKS * Task::MAXIMUM_SIZE;
+
+    fn index(&self, index: u32) -> Result<usize> {
+        let index = index as usize;
+        if index >= self.tasks.len() {
+            return Err(ErrorCode::TaskNotFound.into());
+        }
+        Ok(index)
+    }
+}
+
+/// Shares a list of `owner` with other members.
+#[account]
+pub struct TaskList {
+    owner: Pubkey,        // 32
+    list_index: u32,      // 4
+    members: Vec<Member>, // 4 + (MAXIMUM_MEMBERS * (32 + 1))
+}
+
+impl TaskList {
+    pub const MAXIMUM_MEMBERS: usize = 16;
+    pub const SIZE: usize = 32 + 4 + (4 + Self::MAXIMUM_MEMBERS * (32 + 1));
+
+    fn role_of(&self, key: Pubkey) -> Option<Role> {
+        self.members
+            .iter()
+            .find(|member| member.key == key)
+            .map(|member| member.role)
+    }
+}
+
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct Member {
+    pub key: Pubkey,
+    pub role: Role,
+}
+
+/// Roles are ordered, so that each role can do everything the roles before it can.
+#[derive(
+    Debug, AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub enum Role {
+    /// Can read the tasks.
+    Viewer,
+    /// Can change the tasks.
+    Editor,
+    /// Can also invite and remove members.
+    Admin,
 }
 
 #[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct Task {
     pub id: u32,
-    /// The name of the task. Max 32 characters, min 1 character.
+    /// The name of the task. Max 32 bytes, min 1 byte.
     pub name: String,
     pub completed: bool,
+    /// When the task is due, as a Unix timestamp.
+    pub due_at: Option<i64>,
+    /// From `0` (none) to `3` (highest).
+    pub priority: u8,
+    /// Max 5 tags of 1 to 16 bytes each.
+    pub tags: Vec<String>,
+    /// Max 256 bytes.
+    pub notes: String,
+    /// The id of the task this task is a subtask of.
+    pub parent_id: Option<u32>,
+    pub recurrence: Option<Recurrence>,
+}
+
+/// When a task repeats. `roll_recurring` starts the next occurrence of the task
+/// once `next_due_at` has passed. The `due_at` of the task always equals `next_due_at`.
+#[derive(Debug, AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct Recurrence {
+    /// The number of seconds between occurrences. Must be positive.
+    pub interval: i64,
+    /// When the current occurrence is due, as a Unix timestamp.
+    pub next_due_at: i64,
+}
+
+impl Task {
+    pub const MAXIMUM_SIZE: usize = 4
+        + (4 + MAXIMUM_NAME_LENGTH)
+        + 1
+        + (1 + 8)
+        + 1
+        + (4 + MAXIMUM_TAGS * (4 + MAXIMUM_TAG_LENGTH))
+        + (4 + MAXIMUM_NOTES_LENGTH)
+        + (1 + 4)
+        + (1 + 8 + 8);
+}
+
+/// The number of bytes a value takes up when serialized with Borsh,
+/// computed without serializing it.
+trait SerializedSize {
+    fn serialized_size(&self) -> usize;
+}
+
+impl SerializedSize for str {
+    fn serialized_size(&self) -> usize {
+        4 + self.len()
+    }
+}
+
+impl SerializedSize for String {
+    fn serialized_size(&self) -> usize {
+        self.as_str().serialized_size()
+    }
+}
+
+impl<T: SerializedSize> SerializedSize for [T] {
+    fn serialized_size(&self) -> usize {
+        4 + self.iter().map(T::serialized_size).sum::<usize>()
+    }
+}
+
+impl SerializedSize for Task {
+    fn serialized_size(&self) -> usize {
+        4 + self.name.serialized_size()
+            + 1
+            + (1 + self.due_at.map_or(0, |_| 8))
+            + 1
+            + self.tags.serialized_size()
+            + self.notes.serialized_size()
+            + (1 + self.parent_id.map_or(0, |_| 4))
+            + (1 + self.recurrence.map_or(0, |_| 8 + 8))
+    }
+}
+
+#[event]
+pub struct TaskCreated {
+    /// The tasks account of the list.
+    pub list: Pubkey,
+    pub user: Pubkey,
+    pub id: u32,
+}
+
+#[event]
+pub struct TaskCompleted {
+    pub list: Pubkey,
+    pub user: Pubkey,
+    pub id: u32,
+}
+
+#[event]
+pub struct TaskDeleted {
+    pub list: Pubkey,
+    pub user: Pubkey,
+    pub id: u32,
+}
+
+#[event]
+pub struct TasksReplaced {
+    pub list: Pubkey,
+    pub user: Pubkey,
+    pub task_count: u32,
 }
 
 #[error_code]
 pub enum ErrorCode {
-    #[msg("The task name must be less than 32 characters.")]
+    #[msg("The task name must be at most 32 bytes.")]
     TaskNameTooLong,
-    #[msg("The task name must be at least 1 character.")]
+    #[msg("The task name must be at least 1 byte.")]
     TaskNameTooShort,
     #[msg("The task id must be unique.")]
     TaskIdNotUnique,
+    #[msg("No task with the given id exists.")]
+    TaskNotFound,
+    #[msg("The reordered ids must list every task exactly once.")]
+    InvalidTaskOrder,
+    #[msg("The task priority must be at most 3.")]
+    InvalidPriority,
+    #[msg("A task can have at most 5 tags.")]
+    TooManyTags,
+    #[msg("A tag must be at most 16 bytes.")]
+    TagTooLong,
+    #[msg("A tag must be at least 1 byte.")]
+    TagTooShort,
+    #[msg("The task notes must be at most 256 bytes.")]
+    NotesTooLong,
+    #[msg("The signer's role does not allow this action.")]
+    Unauthorized,
+    #[msg("The task list has too many members.")]
+    TooManyMembers,
+    #[msg("The key is already a member of the task list.")]
+    AlreadyAMember,
+    #[msg("The key is not a member of the task list.")]
+    NotAMember,
+    #[msg("A user can have at most 16 lists.")]
+    TooManyLists,
+    #[msg("The list name must be at most 32 bytes.")]
+    ListNameTooLong,
+    #[msg("The list name must be at least 1 byte.")]
+    ListNameTooShort,
+    #[msg("No list with the given index exists.")]
+    ListNotFound,
+    #[msg("The list still has incomplete tasks, and closing it was not confirmed.")]
+    IncompleteTasksRemain,
+    #[msg("The page is full.")]
+    PageFull,
+    #[msg("Only empty pages can be closed.")]
+    PageNotEmpty,
+    #[msg("The pages of the list must be closed first.")]
+    PagesRemain,
+    #[msg("The list has no pages.")]
+    NoPages,
+    #[msg("A list can have at most 32 tasks, more tasks can be stored in pages.")]
+    TooManyTasks,
+    #[msg("The parent task does not exist.")]
+    ParentNotFound,
+    #[msg("A task can't be below itself.")]
+    TaskCycle,
+    #[msg("The task has subtasks, and deleting them was not requested.")]
+    TaskHasChildren,
+    #[msg("A recurring task needs a positive interval, and is due at its next occurrence.")]
+    InvalidRecurrence,
+    #[msg("The list is shared, and must be closed with close_list.")]
+    ListIsShared,
 }
@@ -0,0 +1,136 @@
+import * as anchor from '@coral-xyz/anchor';
+import { AnchorError, Program } from '@coral-xyz/anchor';
+import { Todo } from '../target/types/todo';
+import {
+  Connection,
+  Keypair,
+  PublicKey,
+  SYSVAR_CLOCK_PUBKEY
+} from '@solana/web3.js';
+import { assert, expect } from 'chai';
+
+const DAY = 24 * 60 * 60;
+
+describe('recurring tasks', () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+
+  const program = anchor.workspace.Todo as Program<Todo>;
+  const connection = new Connection('http://localhost:8899', 'confirmed');
+
+  it('rolls completed recurring tasks once they are past due', async () => {
+    const user = Keypair.generate();
+    const sig = await connection.requestAirdrop(user.publicKey, 1_000_000_000);
+    await connection.confirmTransaction(sig);
+
+    const [directoryPublicKey] = PublicKey.findProgramAddressSync(
+      [Buffer.from('list_directory'), user.publicKey.toBuffer()],
+      program.programId
+    );
+    const [tasksPublicKey] = PublicKey.findProgramAddressSync(
+      [user.publicKey.toBuffer(), Buffer.alloc(4)],
+      program.programId
+    );
+    const accounts = {
+      tasks: tasksPublicKey,
+      taskList: null,
+      owner: user.publicKey,
+      user: user.publicKey
+    };
+    await program.methods
+      .createList('chores')
+      .accounts({
+        directory: directoryPublicKey,
+        tasks: tasksPublicKey,
+        owner: user.publicKey
+      })
+      .signers([user])
+      .rpc();
+
+    // The validator clock can't be moved, so the tasks start out
+    // with their due times already behind or ahead of it.
+    const now = await unixTimestamp(connection);
+    const daily = (nextDueAt: number) => ({
+      dueAt: new anchor.BN(nextDueAt),
+      recurrence: {
+        interval: new anchor.BN(DAY),
+        nextDueAt: new anchor.BN(nextDueAt)
+      }
+    });
+    await program.methods
+      .saveTasks(0, [
+        { ...task(1, 'water plants'), completed: true, ...daily(now + DAY) },
+        { ...task(2, 'take out trash'), completed: true, ...daily(now - DAY) },
+        { ...task(3, 'pay rent'), completed: true, ...daily(now - 4 * DAY) },
+        { ...task(4, 'mop floors'), ...daily(now - DAY) },
+        { ...task(5, 'file taxes'), completed: true }
+      ])
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+
+    await program.methods
+      .rollRecurring(0)
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+
+    const tasksAccount = await program.account.tasksAccount.fetch(
+      tasksPublicKey
+    );
+    expect(
+      tasksAccount.tasks.map(({ id, completed, dueAt, recurrence }) => ({
+        id,
+        completed,
+        dueAt: dueAt?.toNumber() ?? null,
+        nextDueAt: recurrence?.nextDueAt.toNumber() ?? null
+      }))
+    ).to.eql([
+      // nothing is due yet
+      { id: 1, completed: true, dueAt: now + DAY, nextDueAt: now + DAY },
+      { id: 2, completed: false, dueAt: now + DAY, nextDueAt: now + DAY },
+      // missed occurrences are skipped
+      { id: 3, completed: false, dueAt: now + DAY, nextDueAt: now + DAY },
+      // incomplete tasks stay overdue
+      { id: 4, completed: false, dueAt: now - DAY, nextDueAt: now - DAY },
+      { id: 5, completed: true, dueAt: null, nextDueAt: null }
+    ]);
+
+    try {
+      await program.methods
+        .saveTasks(0, [
+          { ...task(1, 'water plants'), ...daily(now), dueAt: null }
+        ])
+        .accounts(accounts)
+        .signers([user])
+        .rpc();
+      assert.fail('expected InvalidRecurrence');
+    } catch (e) {
+      expect(e).to.be.instanceOf(AnchorError);
+      expect((e as AnchorError).error.errorCode.code).to.equal(
+        'InvalidRecurrence'
+      );
+    }
+  });
+});
+
+function task(id: number, name: string) {
+  return {
+    id,
+    name,
+    completed: false,
+    dueAt: null as anchor.BN | null,
+    priority: 0,
+    tags: [],
+    notes: '',
+    parentId: null,
+    recurrence: null as { interval: anchor.BN; nextDueAt: anchor.BN } | null
+  };
+}
+
+/**
+ * Reads `unix_timestamp` from the clock sysvar, the time `Clock::get` returns.
+ */
+async function unixTimestamp(connection: Connection): Promise<number> {
+  const clock = await connection.getAccountInfo(SYSVAR_CLOCK_PUBKEY);
+  return Number(clock.data.readBigInt64LE(32));
+}
@@ -1,7 +1,8 @@
 import * as anchor from '@coral-xyz/anchor';
-import { Program } from '@coral-xyz/anchor';
+import { AnchorError, Program } from '@coral-xyz/anchor';
 import { Todo } from '../target/types/todo';
 import { Connection, Keypair, PublicKey } from '@solana/web3.js';
+import { assert, expect } from 'chai';
 
 describe('todo', () => {
   // Configure the client to use the local cluster.
@@ -15,23 +16,1022 @@ describe('todo', () => {
     const sig = await connection.requestAirdrop(user.publicKey, 10_000_000_000);
     await connection.confirmTransaction(sig);
 
+    const [directoryPublicKey] = PublicKey.findProgramAddressSync(
+      [Buffer.from('list_directory'), user.publicKey.toBuffer()],
+      program.programId
+    );
     const [tasksPublicKey, _] = PublicKey.findProgramAddressSync(
-      [user.publicKey.toBuffer()],
+      [user.publicKey.toBuffer(), u32Seed(0)],
       program.programId
     );
+    await program.methods
+      .createList('example')
+      .accounts({
+        directory: directoryPublicKey,
+        tasks: tasksPublicKey,
+        owner: user.publicKey
+      })
+      .signers([user])
+      .rpc();
     const _tx = await program.methods
-      .saveTasks([
-        {
-          id: 1,
-          name: 'example',
-          completed: false
-        }
-      ])
-      .accounts({ user: user.publicKey, tasks: tasksPublicKey })
+      .saveTasks(0, [newTask(1, 'example')])
+      .accounts({
+        user: user.publicKey,
+        owner: user.publicKey,
+        tasks: tasksPublicKey,
+        taskList: null
+      })
       .signers([user])
       .rpc({ skipPreflight: true });
     const tasks = await program.account.tasksAccount.fetch(tasksPublicKey);
 
     console.log('tasks', tasks);
   });
+
+  it('edits single tasks by id', async () => {
+    const { user, tasksPublicKey, accounts } = await createUser(
+      program,
+      connection
+    );
+
+    for (const [id, name] of [
+      [1, 'first'],
+      [2, 'second'],
+      [3, 'third']
+    ] as const) {
+      await program.methods
+        .addTask(0, newTask(id, name))
+        .accounts(accounts)
+        .signers([user])
+        .rpc();
+    }
+
+    await program.methods
+      .updateTask(0, 2, 'renamed')
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+    await program.methods
+      .toggleTask(0, 3, false)
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+    await program.methods
+      .deleteTask(0, 1, false)
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+    await program.methods
+      .reorderTasks(0, [3, 2])
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+
+    const tasksAccount = await program.account.tasksAccount.fetch(
+      tasksPublicKey
+    );
+    expect(tasksAccount.tasks).to.eql([
+      { ...newTask(3, 'third'), completed: true },
+      newTask(2, 'renamed')
+    ]);
+  });
+
+  it('rejects unknown and duplicate task ids', async () => {
+    const { user, tasksPublicKey, accounts } = await createUser(
+      program,
+      connection
+    );
+
+    await program.methods
+      .addTask(0, newTask(1, 'first'))
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+
+    const calls = [
+      [
+        'TaskIdNotUnique',
+        program.methods.addTask(0, newTask(1, 'again'))
+      ],
+      ['TaskNotFound', program.methods.updateTask(0, 2, 'missing')],
+      ['TaskNotFound', program.methods.toggleTask(0, 2, false)],
+      ['TaskNotFound', program.methods.deleteTask(0, 2, false)],
+      ['TaskNotFound', program.methods.reorderTasks(0, [2])],
+      ['InvalidTaskOrder', program.methods.reorderTasks(0, [1, 1])]
+    ] as const;
+    for (const [code, call] of calls) {
+      try {
+        await call
+          .accounts(accounts)
+          .signers([user])
+          .rpc();
+        assert.fail(`expected ${code}`);
+      } catch (e) {
+        expect(e).to.be.instanceOf(AnchorError);
+        expect((e as AnchorError).error.errorCode.code).to.equal(code);
+      }
+    }
+  });
+
+  it('refunds rent when the task list shrinks', async () => {
+    const { user, tasksPublicKey, accounts } = await createUser(
+      program,
+      connection
+    );
+
+    const tasks = [1, 2, 3].map(id => newTask(id, `task ${id}`));
+    await program.methods
+      .saveTasks(0, tasks)
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+
+    const shrinkings = [
+      [tasks.slice(0, 1), program.methods.saveTasks(0, tasks.slice(0, 1))],
+      [[], program.methods.deleteTask(0, 1, false)]
+    ] as const;
+    for (const [remainingTasks, call] of shrinkings) {
+      const userBalanceBefore = await connection.getBalance(user.publicKey);
+      const tasksBalanceBefore = await connection.getBalance(tasksPublicKey);
+
+      await call
+        .accounts(accounts)
+        .signers([user])
+        .rpc();
+
+      const rent = await connection.getMinimumBalanceForRentExemption(
+        tasksAccountSpace(remainingTasks)
+      );
+      const tasksAccountInfo = await connection.getAccountInfo(tasksPublicKey);
+      expect(tasksAccountInfo.data.length).to.equal(
+        tasksAccountSpace(remainingTasks)
+      );
+      expect(tasksAccountInfo.lamports).to.equal(rent);
+      // the provider wallet pays the transaction fees
+      expect(await connection.getBalance(user.publicKey)).to.equal(
+        userBalanceBefore + tasksBalanceBefore - rent
+      );
+    }
+  });
+
+  it('saves a maximal list of tasks', async () => {
+    const { user, tasksPublicKey, accounts } = await createUser(
+      program,
+      connection
+    );
+
+    // The most tasks with 32 byte names and no other details
+    // that fit in a single transaction.
+    // Names of multi-byte characters are limited by their length in bytes.
+    const tasks = Array.from({ length: 16 }, (_, i) =>
+      newTask(i, i % 2 ? '\u{1F4DD}'.repeat(8) : `${i}`.padEnd(32, '.'))
+    );
+    await program.methods
+      .saveTasks(0, tasks)
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+
+    const tasksAccountInfo = await connection.getAccountInfo(tasksPublicKey);
+    expect(tasksAccountInfo.data.length).to.equal(tasksAccountSpace(tasks));
+    const tasksAccount = await program.account.tasksAccount.fetch(
+      tasksPublicKey
+    );
+    expect(tasksAccount.tasks).to.eql(tasks);
+
+    try {
+      await program.methods
+        .updateTask(0, 1, '\u{1F4DD}'.repeat(9))
+        .accounts(accounts)
+        .signers([user])
+        .rpc();
+      assert.fail('expected TaskNameTooLong');
+    } catch (e) {
+      expect(e).to.be.instanceOf(AnchorError);
+      expect((e as AnchorError).error.errorCode.code).to.equal(
+        'TaskNameTooLong'
+      );
+    }
+  });
+
+  it('plans tasks with due dates, priorities, tags and notes', async () => {
+    const { user, tasksPublicKey, accounts } = await createUser(
+      program,
+      connection
+    );
+
+    const dueAt = new anchor.BN(1_700_000_000);
+    await program.methods
+      .addTask(0, {
+        ...newTask(1, 'ship it'),
+        dueAt,
+        priority: 3,
+        tags: ['work'],
+        notes: 'Before the weekend.'
+      })
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+    await program.methods
+      .updateTaskDetails(0, 1, null, 1, ['work', 'later'], 'n'.repeat(256))
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+
+    const tasksAccount = await program.account.tasksAccount.fetch(
+      tasksPublicKey
+    );
+    expect(tasksAccount.tasks).to.eql([
+      {
+        ...newTask(1, 'ship it'),
+        priority: 1,
+        tags: ['work', 'later'],
+        notes: 'n'.repeat(256)
+      }
+    ]);
+    const tasksAccountInfo = await connection.getAccountInfo(tasksPublicKey);
+    expect(tasksAccountInfo.data.length).to.equal(
+      tasksAccountSpace(tasksAccount.tasks)
+    );
+
+    const calls = [
+      ['InvalidPriority', [4, [], '']],
+      ['TooManyTags', [0, ['a', 'b', 'c', 'd', 'e', 'f'], '']],
+      ['TagTooLong', [0, ['t'.repeat(17)], '']],
+      ['TagTooShort', [0, [''], '']],
+      ['NotesTooLong', [0, [], 'n'.repeat(257)]]
+    ] as const;
+    for (const [code, [priority, tags, notes]] of calls) {
+      try {
+        await program.methods
+          .updateTaskDetails(0, 1, dueAt, priority, [...tags], notes)
+          .accounts(accounts)
+          .signers([user])
+          .rpc();
+        assert.fail(`expected ${code}`);
+      } catch (e) {
+        expect(e).to.be.instanceOf(AnchorError);
+        expect((e as AnchorError).error.errorCode.code).to.equal(code);
+      }
+    }
+  });
+
+  it('creates and closes lists in the directory', async () => {
+    const { user, directoryPublicKey, tasksPublicKey } = await createUser(
+      program,
+      connection
+    );
+
+    const [groceriesPublicKey] = PublicKey.findProgramAddressSync(
+      [user.publicKey.toBuffer(), u32Seed(1)],
+      program.programId
+    );
+    await program.methods
+      .createList('groceries')
+      .accounts({
+        directory: directoryPublicKey,
+        tasks: groceriesPublicKey,
+        owner: user.publicKey
+      })
+      .signers([user])
+      .rpc();
+
+    let directory = await program.account.listDirectory.fetch(
+      directoryPublicKey
+    );
+    expect(directory.nextIndex).to.equal(2);
+    expect(directory.lists).to.eql([
+      { index: 0, name: 'tasks' },
+      { index: 1, name: 'groceries' }
+    ]);
+
+    await program.methods
+      .closeList(0, false)
+      .accounts({
+        directory: directoryPublicKey,
+        tasks: tasksPublicKey,
+        taskList: null,
+        owner: user.publicKey
+      })
+      .signers([user])
+      .rpc();
+
+    directory = await program.account.listDirectory.fetch(directoryPublicKey);
+    expect(directory.lists).to.eql([{ index: 1, name: 'groceries' }]);
+    expect(await connection.getAccountInfo(tasksPublicKey)).to.be.null;
+  });
+
+  it('refunds rent when a list is closed', async () => {
+    const { user, directoryPublicKey, tasksPublicKey, accounts } =
+      await createUser(program, connection);
+
+    const [taskListPublicKey, _] = PublicKey.findProgramAddressSync(
+      [Buffer.from('task_list'), user.publicKey.toBuffer(), u32Seed(0)],
+      program.programId
+    );
+    await program.methods
+      .createTaskList(0)
+      .accounts({ taskList: taskListPublicKey, owner: user.publicKey })
+      .signers([user])
+      .rpc();
+
+    await program.methods
+      .saveTasks(0, [
+        { ...newTask(1, 'done'), completed: true },
+        newTask(2, 'not done')
+      ])
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+
+    const closeList = (confirmIncomplete: boolean) =>
+      program.methods
+        .closeList(0, confirmIncomplete)
+        .accounts({
+          directory: directoryPublicKey,
+          tasks: tasksPublicKey,
+          taskList: taskListPublicKey,
+          owner: user.publicKey
+        })
+        .signers([user])
+        .rpc();
+
+    try {
+      await closeList(false);
+      assert.fail('expected IncompleteTasksRemain');
+    } catch (e) {
+      expect(e).to.be.instanceOf(AnchorError);
+      expect((e as AnchorError).error.errorCode.code).to.equal(
+        'IncompleteTasksRemain'
+      );
+    }
+
+    const userBalanceBefore = await connection.getBalance(user.publicKey);
+    const tasksBalanceBefore = await connection.getBalance(tasksPublicKey);
+    const taskListBalanceBefore = await connection.getBalance(
+      taskListPublicKey
+    );
+
+    await closeList(true);
+
+    expect(await connection.getAccountInfo(tasksPublicKey)).to.be.null;
+    expect(await connection.getAccountInfo(taskListPublicKey)).to.be.null;
+    // the provider wallet pays the transaction fees
+    expect(await connection.getBalance(user.publicKey)).to.equal(
+      userBalanceBefore + tasksBalanceBefore + taskListBalanceBefore
+    );
+  });
+
+  it('closes the tasks of a list and refunds the user', async () => {
+    const { user, directoryPublicKey, tasksPublicKey, accounts } =
+      await createUser(program, connection);
+
+    await program.methods
+      .saveTasks(0, [newTask(1, 'not done')])
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+
+    const [taskListPublicKey, _] = PublicKey.findProgramAddressSync(
+      [Buffer.from('task_list'), user.publicKey.toBuffer(), u32Seed(0)],
+      program.programId
+    );
+    const closeTasks = (confirmIncomplete: boolean) =>
+      program.methods
+        .closeTasks(0, confirmIncomplete)
+        .accounts({
+          directory: directoryPublicKey,
+          tasks: tasksPublicKey,
+          taskList: taskListPublicKey,
+          user: user.publicKey
+        })
+        .signers([user])
+        .rpc();
+
+    try {
+      await closeTasks(false);
+      assert.fail('expected IncompleteTasksRemain');
+    } catch (e) {
+      expect(e).to.be.instanceOf(AnchorError);
+      expect((e as AnchorError).error.errorCode.code).to.equal(
+        'IncompleteTasksRemain'
+      );
+    }
+
+    const userBalanceBefore = await connection.getBalance(user.publicKey);
+    const tasksBalanceBefore = await connection.getBalance(tasksPublicKey);
+
+    await closeTasks(true);
+
+    const directory = await program.account.listDirectory.fetch(
+      directoryPublicKey
+    );
+    expect(directory.lists).to.eql([]);
+    expect(await connection.getAccountInfo(tasksPublicKey)).to.be.null;
+    // the provider wallet pays the transaction fees
+    expect(await connection.getBalance(user.publicKey)).to.equal(
+      userBalanceBefore + tasksBalanceBefore
+    );
+  });
+
+  it('stores tasks in pages', async () => {
+    const { user, directoryPublicKey, tasksPublicKey, accounts } =
+      await createUser(program, connection);
+
+    const pages = [0, 1].map(
+      pageIndex =>
+        PublicKey.findProgramAddressSync(
+          [Buffer.from('page'), tasksPublicKey.toBuffer(), u32Seed(pageIndex)],
+          program.programId
+        )[0]
+    );
+    for (const page of pages) {
+      await program.methods
+        .appendPage(0)
+        .accounts({ ...accounts, page })
+        .signers([user])
+        .rpc();
+    }
+
+    for (let id = 0; id < 16; id++) {
+      await program.methods
+        .addPageTask(0, 1, newTask(id, `task ${id}`))
+        .accounts({ ...accounts, page: pages[1] })
+        .signers([user])
+        .rpc();
+    }
+    await program.methods
+      .updatePageTask(0, 1, 2, { ...newTask(2, 'task 2'), completed: true })
+      .accounts({ ...accounts, page: pages[1] })
+      .signers([user])
+      .rpc();
+    await program.methods
+      .deletePageTask(0, 1, 0)
+      .accounts({ ...accounts, page: pages[1] })
+      .signers([user])
+      .rpc();
+
+    const tasksAccount = await program.account.tasksAccount.fetch(
+      tasksPublicKey
+    );
+    expect(tasksAccount.pageCount).to.equal(2);
+    const page = await program.account.taskPage.fetch(pages[1]);
+    expect(page.tasks).to.have.length(15);
+    expect(page.tasks[1]).to.eql({ ...newTask(2, 'task 2'), completed: true });
+
+    const closePage = (page: PublicKey) =>
+      program.methods
+        .closePage(0)
+        .accounts({ ...accounts, page })
+        .signers([user])
+        .rpc();
+    const calls = [
+      [
+        'PageFull',
+        () =>
+          program.methods
+            .addPageTask(0, 1, newTask(0, 'task 0'))
+            .accounts({ ...accounts, page: pages[1] })
+            .signers([user])
+            .rpc()
+      ],
+      [
+        'TaskNotFound',
+        () =>
+          program.methods
+            .deletePageTask(0, 0, 0)
+            .accounts({ ...accounts, page: pages[0] })
+            .signers([user])
+            .rpc()
+      ],
+      ['PageNotEmpty', () => closePage(pages[1])],
+      [
+        'PagesRemain',
+        () =>
+          program.methods
+            .closeList(0, true)
+            .accounts({
+              directory: directoryPublicKey,
+              tasks: tasksPublicKey,
+              taskList: null,
+              owner: user.publicKey
+            })
+            .signers([user])
+            .rpc()
+      ]
+    ] as const;
+    for (const [code, call] of calls) {
+      try {
+        await call();
+        assert.fail(`expected ${code}`);
+      } catch (e) {
+        expect(e).to.be.instanceOf(AnchorError);
+        expect((e as AnchorError).error.errorCode.code).to.equal(code);
+      }
+    }
+
+    for (let index = 14; index >= 0; index--) {
+      await program.methods
+        .deletePageTask(0, 1, index)
+        .accounts({ ...accounts, page: pages[1] })
+        .signers([user])
+        .rpc();
+    }
+    await closePage(pages[1]);
+    await closePage(pages[0]);
+
+    expect(await connection.getAccountInfo(pages[1])).to.be.null;
+    expect(await connection.getAccountInfo(pages[0])).to.be.null;
+    expect(
+      (await program.account.tasksAccount.fetch(tasksPublicKey)).pageCount
+    ).to.equal(0);
+  });
+
+  it('emits events and counts created and completed tasks', async () => {
+    const { user, tasksPublicKey, accounts } = await createUser(
+      program,
+      connection
+    );
+
+    const calls = [
+      [program.methods.addTask(0, newTask(1, 'first')), [['taskCreated', 1]]],
+      [
+        program.methods.addTask(0, {
+          ...newTask(2, 'second'),
+          completed: true
+        }),
+        [
+          ['taskCreated', 2],
+          ['taskCompleted', 2]
+        ]
+      ],
+      [program.methods.toggleTask(0, 1, false), [['taskCompleted', 1]]],
+      [program.methods.toggleTask(0, 1, false), []],
+      [program.methods.deleteTask(0, 2, false), [['taskDeleted', 2]]],
+      [
+        program.methods.saveTasks(0, [
+          { ...newTask(1, 'first'), completed: true },
+          newTask(3, 'third')
+        ]),
+        [
+          ['taskCompleted', 1],
+          ['taskCreated', 3],
+          ['tasksReplaced', 2]
+        ]
+      ]
+    ] as const;
+    for (const [call, expectedEvents] of calls) {
+      const signature = await call
+        .accounts(accounts)
+        .signers([user])
+        .rpc({ commitment: 'confirmed' });
+
+      const events = await emittedEvents(program, signature);
+      expect(
+        events.map(({ name, data }) => [name, data.id ?? data.taskCount])
+      ).to.eql(expectedEvents);
+      for (const { data } of events) {
+        expect(data.list).to.eql(tasksPublicKey);
+        expect(data.user).to.eql(user.publicKey);
+      }
+    }
+
+    const tasksAccount = await program.account.tasksAccount.fetch(
+      tasksPublicKey
+    );
+    expect(tasksAccount.createdCount.toNumber()).to.equal(3);
+    expect(tasksAccount.completedCount.toNumber()).to.equal(3);
+  });
+
+  it('saves the largest lists within the compute budget', async () => {
+    const { user, tasksPublicKey, accounts } = await createUser(
+      program,
+      connection
+    );
+
+    // The most tasks a list holds, with ids in reverse order for the sort,
+    // and names short enough for all of them to fit in a transaction.
+    const tasks = Array.from({ length: MAX_TASKS }, (_, i) =>
+      newTask(MAX_TASKS - 1 - i, `${MAX_TASKS - 1 - i}`)
+    );
+    const computeUnits: number[] = [];
+    for (const replacingTasks of [
+      tasks,
+      tasks.map(task => ({ ...task, completed: true }))
+    ]) {
+      const signature = await program.methods
+        .saveTasks(0, replacingTasks)
+        .accounts(accounts)
+        .signers([user])
+        .rpc({ commitment: 'confirmed' });
+      const transaction = await connection.getTransaction(signature, {
+        commitment: 'confirmed',
+        maxSupportedTransactionVersion: 0
+      });
+      computeUnits.push(transaction.meta.computeUnitsConsumed);
+    }
+    for (const units of computeUnits) {
+      expect(units).to.be.below(SAVE_TASKS_COMPUTE_BUDGET);
+    }
+
+    try {
+      await program.methods
+        .addTask(0, newTask(MAX_TASKS, 'one too many'))
+        .accounts(accounts)
+        .signers([user])
+        .rpc();
+      assert.fail('expected TooManyTasks');
+    } catch (e) {
+      expect(e).to.be.instanceOf(AnchorError);
+      expect((e as AnchorError).error.errorCode.code).to.equal('TooManyTasks');
+    }
+    const tasksAccount = await program.account.tasksAccount.fetch(
+      tasksPublicKey
+    );
+    expect(tasksAccount.tasks).to.have.length(MAX_TASKS);
+  });
+
+  it('nests subtasks under parent tasks', async () => {
+    const { user, tasksPublicKey, accounts } = await createUser(
+      program,
+      connection
+    );
+
+    // 1 > 2 > 3, and 4 on its own
+    await program.methods
+      .saveTasks(0, [
+        newTask(1, 'trip'),
+        { ...newTask(2, 'pack'), parentId: 1 },
+        { ...newTask(3, 'socks'), parentId: 2 },
+        newTask(4, 'water plants')
+      ])
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+
+    const calls = [
+      [
+        'ParentNotFound',
+        program.methods.addTask(0, { ...newTask(5, 'orphan'), parentId: 9 })
+      ],
+      [
+        'ParentNotFound',
+        program.methods.saveTasks(0, [{ ...newTask(1, 'trip'), parentId: 2 }])
+      ],
+      ['TaskCycle', program.methods.setTaskParent(0, 1, 3)],
+      ['TaskCycle', program.methods.setTaskParent(0, 4, 4)],
+      ['TaskHasChildren', program.methods.deleteTask(0, 2, false)]
+    ] as const;
+    for (const [code, call] of calls) {
+      try {
+        await call.accounts(accounts).signers([user]).rpc();
+        assert.fail(`expected ${code}`);
+      } catch (e) {
+        expect(e).to.be.instanceOf(AnchorError);
+        expect((e as AnchorError).error.errorCode.code).to.equal(code);
+      }
+    }
+
+    await program.methods
+      .setTaskParent(0, 4, 1)
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+    await program.methods
+      .toggleTask(0, 1, true)
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+
+    let tasksAccount = await program.account.tasksAccount.fetch(
+      tasksPublicKey
+    );
+    expect(
+      tasksAccount.tasks.map(({ id, completed, parentId }) => ({
+        id,
+        completed,
+        parentId
+      }))
+    ).to.eql([
+      { id: 1, completed: true, parentId: null },
+      { id: 2, completed: true, parentId: 1 },
+      { id: 3, completed: true, parentId: 2 },
+      { id: 4, completed: true, parentId: 1 }
+    ]);
+
+    await program.methods
+      .deleteTask(0, 2, true)
+      .accounts(accounts)
+      .signers([user])
+      .rpc();
+
+    tasksAccount = await program.account.tasksAccount.fetch(tasksPublicKey);
+    expect(tasksAccount.tasks.map(({ id }) => id)).to.eql([1, 4]);
+  });
+
+  it('shares tasks with members by role', async () => {
+    const owner = await createUser(program, connection);
+    const [admin, editor, viewer, stranger] = await Promise.all(
+      [0, 1, 2, 3].map(() => createUser(program, connection))
+    );
+
+    const [taskListPublicKey, _] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('task_list'),
+        owner.user.publicKey.toBuffer(),
+        u32Seed(0)
+      ],
+      program.programId
+    );
+    await program.methods
+      .createTaskList(0)
+      .accounts({ taskList: taskListPublicKey, owner: owner.user.publicKey })
+      .signers([owner.user])
+      .rpc();
+
+    const invitations = [
+      [owner, admin, { admin: {} }],
+      [admin, editor, { editor: {} }],
+      [admin, viewer, { viewer: {} }]
+    ] as const;
+    for (const [inviter, member, role] of invitations) {
+      await program.methods
+        .inviteMember(member.user.publicKey, role)
+        .accounts({ taskList: taskListPublicKey, user: inviter.user.publicKey })
+        .signers([inviter.user])
+        .rpc();
+    }
+
+    const sharedAccounts = (member: typeof owner) => ({
+      ...owner.accounts,
+      taskList: taskListPublicKey,
+      user: member.user.publicKey
+    });
+
+    await program.methods
+      .addTask(0, newTask(1, 'shared'))
+      .accounts(sharedAccounts(editor))
+      .signers([editor.user])
+      .rpc();
+    await program.methods
+      .toggleTask(0, 1, false)
+      .accounts(sharedAccounts(admin))
+      .signers([admin.user])
+      .rpc();
+
+    const tasksAccount = await program.account.tasksAccount.fetch(
+      owner.tasksPublicKey
+    );
+    expect(tasksAccount.tasks).to.eql([
+      { ...newTask(1, 'shared'), completed: true }
+    ]);
+
+    try {
+      await program.methods
+        .toggleTask(0, 1, false)
+        .accounts(sharedAccounts(viewer))
+        .signers([viewer.user])
+        .rpc();
+      assert.fail('expected Unauthorized');
+    } catch (e) {
+      expect(e).to.be.instanceOf(AnchorError);
+      expect((e as AnchorError).error.errorCode.code).to.equal('Unauthorized');
+    }
+
+    // the editor leaves, and the admin removes the viewer
+    await program.methods
+      .removeMember(editor.user.publicKey)
+      .accounts({ taskList: taskListPublicKey, user: editor.user.publicKey })
+      .signers([editor.user])
+      .rpc();
+    await program.methods
+      .removeMember(viewer.user.publicKey)
+      .accounts({ taskList: taskListPublicKey, user: admin.user.publicKey })
+      .signers([admin.user])
+      .rpc();
+
+    const taskList = await program.account.taskList.fetch(taskListPublicKey);
+    expect(taskList.members).to.eql([
+      { key: admin.user.publicKey, role: { admin: {} } }
+    ]);
+
+    const calls = [
+      [
+        'Unauthorized',
+        editor,
+        program.methods.toggleTask(0, 1, false).accounts(sharedAccounts(editor))
+      ],
+      [
+        'Unauthorized',
+        stranger,
+        program.methods
+          .deleteTask(0, 1, false)
+          .accounts(sharedAccounts(stranger))
+      ],
+      [
+        'Unauthorized',
+        stranger,
+        program.methods
+          .inviteMember(stranger.user.publicKey, { admin: {} })
+          .accounts({
+            taskList: taskListPublicKey,
+            user: stranger.user.publicKey
+          })
+      ],
+      [
+        'AlreadyAMember',
+        owner,
+        program.methods
+          .inviteMember(admin.user.publicKey, { viewer: {} })
+          .accounts({
+            taskList: taskListPublicKey,
+            user: owner.user.publicKey
+          })
+      ],
+      [
+        'NotAMember',
+        admin,
+        program.methods.removeMember(editor.user.publicKey).accounts({
+          taskList: taskListPublicKey,
+          user: admin.user.publicKey
+        })
+      ]
+    ] as const;
+    for (const [code, signer, call] of calls) {
+      try {
+        await call.signers([signer.user]).rpc();
+        assert.fail(`expected ${code}`);
+      } catch (e) {
+        expect(e).to.be.instanceOf(AnchorError);
+        expect((e as AnchorError).error.errorCode.code).to.equal(code);
+      }
+    }
+  });
+
+  it('charges rent to the member and refunds it to the owner', async () => {
+    const owner = await createUser(program, connection);
+    const editor = await createUser(program, connection);
+
+    const [taskListPublicKey, _] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('task_list'),
+        owner.user.publicKey.toBuffer(),
+        u32Seed(0)
+      ],
+      program.programId
+    );
+    await program.methods
+      .createTaskList(0)
+      .accounts({ taskList: taskListPublicKey, owner: owner.user.publicKey })
+      .signers([owner.user])
+      .rpc();
+    await program.methods
+      .inviteMember(editor.user.publicKey, { editor: {} })
+      .accounts({ taskList: taskListPublicKey, user: owner.user.publicKey })
+      .signers([owner.user])
+      .rpc();
+
+    const editorAccounts = {
+      ...owner.accounts,
+      taskList: taskListPublicKey,
+      user: editor.user.publicKey
+    };
+    const balances = async () => ({
+      owner: await connection.getBalance(owner.user.publicKey),
+      editor: await connection.getBalance(editor.user.publicKey),
+      tasks: await connection.getBalance(owner.tasksPublicKey)
+    });
+    const rent = (tasks: ReturnType<typeof newTask>[]) =>
+      connection.getMinimumBalanceForRentExemption(tasksAccountSpace(tasks));
+
+    // the provider wallet pays the transaction fees
+    const task = newTask(1, 'shared');
+    let before = await balances();
+    await program.methods
+      .addTask(0, task)
+      .accounts(editorAccounts)
+      .signers([editor.user])
+      .rpc();
+    const grown = await rent([task]);
+    expect(await balances()).to.eql({
+      owner: before.owner,
+      editor: before.editor - (grown - before.tasks),
+      tasks: grown
+    });
+
+    before = await balances();
+    await program.methods
+      .deleteTask(0, 1, false)
+      .accounts(editorAccounts)
+      .signers([editor.user])
+      .rpc();
+    const shrunk = await rent([]);
+    expect(await balances()).to.eql({
+      owner: before.owner + (before.tasks - shrunk),
+      editor: before.editor,
+      tasks: shrunk
+    });
+  });
 });
+
+// Mirrors `MAX_TASKS` in the program.
+const MAX_TASKS = 32;
+
+// Upper bound on the compute units of saving `MAX_TASKS` tasks.
+// Update alongside any change to the `save_tasks` checks.
+const SAVE_TASKS_COMPUTE_BUDGET = 80_000;
+
+function newTask(id: number, name: string) {
+  return {
+    id,
+    name,
+    completed: false,
+    dueAt: null as anchor.BN | null,
+    priority: 0,
+    tags: [] as string[],
+    notes: '',
+    parentId: null as number | null,
+    recurrence: null as { interval: anchor.BN; nextDueAt: anchor.BN } | null
+  };
+}
+
+// Mirrors `TasksAccount::space` in the program.
+function tasksAccountSpace(
+  tasks: ReadonlyArray<ReturnType<typeof newTask>>
+): number {
+  return (
+    8 +
+    4 +
+    tasks.reduce(
+      (size, task) =>
+        size +
+        4 +
+        (4 + Buffer.byteLength(task.name)) +
+        1 +
+        (task.dueAt === null ? 1 : 1 + 8) +
+        1 +
+        task.tags.reduce(
+          (tagsSize, tag) => tagsSize + 4 + Buffer.byteLength(tag),
+          4
+        ) +
+        (4 + Buffer.byteLength(task.notes)) +
+        (task.parentId === null ? 1 : 1 + 4) +
+        (task.recurrence === null ? 1 : 1 + 8 + 8),
+      0
+    ) +
+    4 +
+    8 +
+    8
+  );
+}
+
+async function createUser(program: Program<Todo>, connection: Connection) {
+  const user = Keypair.generate();
+
+  const sig = await connection.requestAirdrop(user.publicKey, 10_000_000_000);
+  await connection.confirmTransaction(sig);
+
+  const [directoryPublicKey] = PublicKey.findProgramAddressSync(
+    [Buffer.from('list_directory'), user.publicKey.toBuffer()],
+    program.programId
+  );
+  const [tasksPublicKey, _] = PublicKey.findProgramAddressSync(
+    [user.publicKey.toBuffer(), u32Seed(0)],
+    program.programId
+  );
+  await program.methods
+    .createList('tasks')
+    .accounts({
+      directory: directoryPublicKey,
+      tasks: tasksPublicKey,
+      owner: user.publicKey
+    })
+    .signers([user])
+    .rpc();
+
+  const accounts = {
+    user: user.publicKey,
+    owner: user.publicKey,
+    tasks: tasksPublicKey,
+    taskList: null as PublicKey | null
+  };
+
+  return { user, directoryPublicKey, tasksPublicKey, accounts };
+}
+
+async function emittedEvents(program: Program<Todo>, signature: string) {
+  const transaction = await program.provider.connection.getTransaction(
+    signature,
+    { commitment: 'confirmed' }
+  );
+  const eventParser = new anchor.EventParser(
+    program.programId,
+    new anchor.BorshCoder(program.idl)
+  );
+
+  return Array.from(eventParser.parseLogs(transaction.meta.logMessages));
+}
+
+function u32Seed(value: number): Buffer {
+  return new anchor.BN(value).toArrayLike(Buffer, 'le', 4);
+}